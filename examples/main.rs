@@ -1,43 +1,53 @@
-use multimarkov::MultiMarkov;
+use multimarkov::{GenerationOptions, MultiMarkov};
 use rand::{rngs::SmallRng, SeedableRng};
+use std::error::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-fn main() {
+fn main() -> Result<(), Box<dyn Error>> {
 
     // initialize logging
     env_logger::builder().filter_level(log::LevelFilter::Debug).init();
 
 
-    let file = File::open("resources/romans.txt").unwrap();
+    let file = File::open("resources/romans.txt")?;
     let reader = BufReader::new(file);
     let lines = reader
         .lines()
-        .map(|l| l.unwrap().to_lowercase())
-        .map(|l| l.chars().collect::<Vec<_>>())
-        .map(|mut v| {
-            v.insert(0, '#');
-            v.push('#');
-            v
-        });
-
-    let mut mm = MultiMarkov::<char>::builder()
-        .with_order(3)
+        .map(|l| l.map(|l| l.to_lowercase().chars().collect::<Vec<_>>()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut builder = MultiMarkov::<char>::builder()
+        .try_with_order(3)?
         .with_prior(0.02)
-        .with_rng(Box::new(SmallRng::seed_from_u64(1234)))
-        .train(lines)
-        .build();
+        .with_boundaries('#', '#') // wrap each name in beginning-of-word and end-of-word characters
+        .with_training_set() // remember the real names, so we can avoid generating them
+        .with_rng(Box::new(SmallRng::seed_from_u64(1234)));
+    builder.try_train(lines.iter())?; // fail if any name can't be trained
+    let mut mm = builder.try_build()?;
+
+    let options = GenerationOptions::new()
+        .with_min_length(4)
+        .with_max_length(10)
+        .with_min_edit_distance(1);
 
     for _i in 0..10 {
-        // generate a roman-sounding name
-        let mut name = vec!['#']; // the beginning-of-word and end-of-word character
-        name.push(mm.random_next(&name).unwrap());
-        while !name.ends_with(&*vec!['#']) {
-            name.push(mm.random_next(&name).unwrap());
+        // generate a new roman-sounding name of 4 to 10 letters
+        match mm.generate_with(&options) {
+            Ok(name) => println!("{}", name.iter().collect::<String>()),
+            Err(e) => println!("generation failed: {}", e),
         }
-        name.pop();
-        name.remove(0);
-        let stringname = name.iter().collect::<String>();
-        println!("{}", stringname);
     }
+
+    let options = options.with_suffix(vec!['i', 'u', 's']);
+
+    for _i in 0..5 {
+        // generate a name that ends in "-ius"
+        match mm.generate_with(&options) {
+            Ok(name) => println!("{}", name.iter().collect::<String>()),
+            Err(e) => println!("generation failed: {}", e),
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,521 @@
+//! Import and export of the ARPA back-off n-gram format, to exchange models with language
+//! modelling toolkits such as SRILM and KenLM.
+//!
+//! A model of order `n` is written as 1-grams to `(n + 1)`-grams: each context in the Markov
+//! chain, followed by each state that can follow it, with the log10 probability of that
+//! transition (including any prior weights).  The model's boundary states are written as the conventional `<s>` and `</s>`.
+//! Since `MultiMarkov` only backs off to a shorter context when there is no model for the longer
+//! one, every context that has a model is written with a back-off weight of -99 (no back-off),
+//! and other contexts get none (free back-off).  Unigram probabilities, which `MultiMarkov` has
+//! no use for, are the average of the probabilities given by its single-state contexts.
+//!
+//! When a model is imported, the missing transitions of every context are filled in from the
+//! shorter contexts it backs off to, scaled by its back-off weight, so that the imported model
+//! draws states with the same probabilities as the ARPA model.  ARPA files don't distinguish
+//! observed transitions from prior weights, so all of them are imported as observed, and the
+//! imported model has no prior.
+
+use crate::error::FormatError;
+use crate::{default_rng, Boundaries, MultiMarkov, SamplingOptions};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::hash::Hash;
+use std::io::{Read, Write};
+
+/// The word for the start boundary state.
+pub const START_WORD: &str = "<s>";
+
+/// The word for the end boundary state.
+pub const END_WORD: &str = "</s>";
+
+/// The log10 probability (or back-off weight) that stands for zero, by convention.
+const LOG_ZERO: f64 = -99.0;
+
+/// A type of state that can be written as a word in an ARPA file.  It's implemented for `char`
+/// and `String`.
+pub trait ArpaState: Sized {
+    /// The word for this state.  It must not be empty or contain whitespace.
+    fn to_arpa_word(&self) -> String;
+
+    /// The state for a word, or `None` if the word isn't a valid state.
+    fn from_arpa_word(word: &str) -> Option<Self>;
+}
+
+impl ArpaState for char {
+    fn to_arpa_word(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_arpa_word(word: &str) -> Option<Self> {
+        let mut chars = word.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl ArpaState for String {
+    fn to_arpa_word(&self) -> String {
+        self.clone()
+    }
+
+    fn from_arpa_word(word: &str) -> Option<Self> {
+        Some(word.to_string())
+    }
+}
+
+/// The log10 probability and the back-off weight (if any) of an n-gram.
+type Entry = (f64, Option<f64>);
+
+impl<T> MultiMarkov<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord + ArpaState,
+{
+    /// Export the trained model in the ARPA format (see the `arpa` module).
+    ///
+    /// ```
+    /// use multimarkov::MultiMarkov;
+    /// let mm = MultiMarkov::<char>::builder()
+    ///     .with_order(2)
+    ///     .with_boundaries('#', '#')
+    ///     .train(vec![vec!['f', 'o', 'o'], vec!['b', 'a', 'r']].into_iter())
+    ///     .build();
+    /// let mut file: Vec<u8> = Vec::new(); // or a std::fs::File
+    /// mm.write_arpa(&mut file).unwrap();
+    /// let imported = MultiMarkov::<char>::read_arpa(file.as_slice(), mm.boundaries.clone()).unwrap();
+    /// assert_eq!(imported.order, 2);
+    /// ```
+    pub fn write_arpa(&self, mut writer: impl Write) -> Result<(), FormatError> {
+        writer.write_all(self.to_arpa()?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Import a model from an ARPA file.  If `boundaries` are given, the words `<s>` and `</s>`
+    /// are read as its start and end states.  The order of the model is one less than the
+    /// longest n-grams in the file.
+    pub fn read_arpa(
+        mut reader: impl Read,
+        boundaries: Option<Boundaries<T>>,
+    ) -> Result<Self, FormatError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Self::from_arpa(&text, boundaries)
+    }
+
+    /// The trained model in the ARPA format, as text.  Fails if a state can't be written as a
+    /// word (see `ArpaState`).
+    pub fn to_arpa(&self) -> Result<String, FormatError> {
+        let mut ngrams: Vec<BTreeMap<Vec<String>, Entry>> = Vec::new();
+        let mut unigram_mass: BTreeMap<String, f64> = BTreeMap::new();
+        let mut unigram_backoffs: HashSet<String> = HashSet::new();
+        let mut single_state_contexts = 0;
+
+        for (context, observed) in &self.markov_chain {
+            let transitions = self.transitions(observed);
+            let mut history = Vec::with_capacity(context.len() + 1);
+            for (i, state) in context.iter().enumerate() {
+                history.push(self.arpa_word(state, i == 0, false)?);
+            }
+            if context.len() == 1 {
+                single_state_contexts += 1;
+                unigram_backoffs.insert(history[0].clone());
+            }
+            while ngrams.len() < context.len() + 1 {
+                ngrams.push(BTreeMap::new());
+            }
+            let sum_of_weights = transitions.sum();
+            for (state, weight) in transitions.iter() {
+                if weight <= 0.0 {
+                    continue;
+                }
+                let probability = weight / sum_of_weights;
+                let mut ngram = history.clone();
+                ngram.push(self.arpa_word(state, false, true)?);
+                if context.len() == 1 {
+                    *unigram_mass.entry(ngram[1].clone()).or_insert(0.0) += probability;
+                }
+                let mut longer_context = context.clone();
+                longer_context.push(state.clone());
+                let backoff = if (ngram.len() as i32) > self.order {
+                    None
+                } else if self.markov_chain.contains_key(&longer_context) {
+                    Some(LOG_ZERO)
+                } else {
+                    None
+                };
+                ngrams[ngram.len() - 1].insert(ngram, (probability.log10(), backoff));
+            }
+        }
+
+        if ngrams.is_empty() {
+            ngrams.push(BTreeMap::new());
+        }
+        // every word needs a unigram, even if (like the start state) it can't be drawn
+        let words: Vec<String> = ngrams
+            .iter()
+            .flatten()
+            .flat_map(|(n, _)| n.clone())
+            .collect();
+        let unigrams = &mut ngrams[0];
+        for word in words {
+            let log_probability = match unigram_mass.get(&word) {
+                Some(mass) => (mass / single_state_contexts as f64).log10(),
+                None => LOG_ZERO,
+            };
+            let backoff = unigram_backoffs.contains(&word).then_some(LOG_ZERO);
+            unigrams.insert(vec![word], (log_probability, backoff));
+        }
+
+        let mut arpa = String::from("\n\\data\\\n");
+        for (n, entries) in ngrams.iter().enumerate() {
+            writeln!(arpa, "ngram {}={}", n + 1, entries.len()).unwrap();
+        }
+        for (n, entries) in ngrams.iter().enumerate() {
+            writeln!(arpa, "\n\\{}-grams:", n + 1).unwrap();
+            for (ngram, (log_probability, backoff)) in entries {
+                write!(
+                    arpa,
+                    "{}\t{}",
+                    format_log(*log_probability),
+                    ngram.join(" ")
+                )
+                .unwrap();
+                if let Some(backoff) = backoff {
+                    write!(arpa, "\t{}", format_log(*backoff)).unwrap();
+                }
+                arpa.push('\n');
+            }
+        }
+        arpa.push_str("\n\\end\\\n");
+        Ok(arpa)
+    }
+
+    /// Import a model from ARPA text.  See `read_arpa`.
+    pub fn from_arpa(text: &str, boundaries: Option<Boundaries<T>>) -> Result<Self, FormatError> {
+        let to_state = |word: &str, line: usize| -> Result<T, FormatError> {
+            match &boundaries {
+                Some(b) if word == START_WORD => Ok(b.start.clone()),
+                Some(b) if word == END_WORD => Ok(b.end.clone()),
+                _ => T::from_arpa_word(word).ok_or_else(|| FormatError::Syntax {
+                    line,
+                    problem: format!("{:?} is not a valid state", word),
+                }),
+            }
+        };
+
+        let ngrams = parse_arpa(text)?;
+        if ngrams.len() < 2 {
+            return Err(FormatError::Corrupt(
+                "the model has no n-grams longer than 1-grams",
+            ));
+        }
+
+        // log10 probabilities of n-grams, grouped by their context (the n-gram without its last
+        // state); and back-off weights of n-grams as contexts
+        let mut explicit: HashMap<Vec<T>, BTreeMap<T, f64>> = HashMap::new();
+        let mut backoffs: HashMap<Vec<T>, f64> = HashMap::new();
+        let mut known_states: BTreeSet<T> = BTreeSet::new();
+        for (line, words, log_probability, backoff) in ngrams.iter().flatten() {
+            let ngram = words
+                .iter()
+                .map(|word| to_state(word, *line))
+                .collect::<Result<Vec<T>, FormatError>>()?;
+            if let Some(backoff) = backoff {
+                backoffs.insert(ngram.clone(), *backoff);
+            }
+            if words.len() == 1 && words[0] != START_WORD && words[0] != END_WORD {
+                known_states.insert(ngram[0].clone());
+            }
+            if *log_probability > LOG_ZERO {
+                let (state, context) = ngram.split_last().unwrap();
+                explicit
+                    .entry(context.to_vec())
+                    .or_default()
+                    .insert(state.clone(), *log_probability);
+            }
+        }
+
+        let mut distributions: HashMap<Vec<T>, BTreeMap<T, f64>> = HashMap::new();
+        let contexts: Vec<Vec<T>> = explicit.keys().filter(|c| !c.is_empty()).cloned().collect();
+        let markov_chain = contexts
+            .into_iter()
+            .map(|context| {
+                let distribution =
+                    backed_off_distribution(&context, &explicit, &backoffs, &mut distributions);
+                (context, distribution)
+            })
+            .collect();
+
+        Ok(MultiMarkov {
+            markov_chain,
+            prior: None,
+            known_states,
+            order: ngrams.len() as i32 - 1,
+            boundaries,
+            training_set: None,
+            sampling: SamplingOptions::new(),
+            rng: default_rng(),
+        })
+    }
+
+    /// The word for a state: its `ArpaState` word, or `<s>` for the start state at the beginning
+    /// of a context, or `</s>` for the end state as a following state.
+    fn arpa_word(&self, state: &T, first: bool, following: bool) -> Result<String, FormatError> {
+        if let Some(boundaries) = &self.boundaries {
+            if first && !following && *state == boundaries.start {
+                return Ok(START_WORD.to_string());
+            }
+            if following && *state == boundaries.end {
+                return Ok(END_WORD.to_string());
+            }
+        }
+        let word = state.to_arpa_word();
+        if word.is_empty()
+            || word.contains(char::is_whitespace)
+            || word == START_WORD
+            || word == END_WORD
+        {
+            return Err(FormatError::UnrepresentableState(word));
+        }
+        Ok(word)
+    }
+}
+
+/// The probabilities (not logarithms) of every state that can follow the given context: its
+/// n-grams' probabilities, and, for states without an n-gram, the probabilities given by the
+/// shorter context it backs off to, scaled by its back-off weight.  Memoized in `distributions`.
+fn backed_off_distribution<T>(
+    context: &[T],
+    explicit: &HashMap<Vec<T>, BTreeMap<T, f64>>,
+    backoffs: &HashMap<Vec<T>, f64>,
+    distributions: &mut HashMap<Vec<T>, BTreeMap<T, f64>>,
+) -> BTreeMap<T, f64>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    if let Some(distribution) = distributions.get(context) {
+        return distribution.clone();
+    }
+    let mut distribution = BTreeMap::new();
+    if !context.is_empty() {
+        let backoff = backoffs.get(context).copied().unwrap_or(0.0);
+        if backoff > LOG_ZERO {
+            let scale = 10f64.powf(backoff);
+            let shorter = backed_off_distribution(&context[1..], explicit, backoffs, distributions);
+            for (state, probability) in shorter {
+                distribution.insert(state, probability * scale);
+            }
+        }
+    }
+    for (state, log_probability) in explicit.get(context).into_iter().flatten() {
+        distribution.insert(state.clone(), 10f64.powf(*log_probability));
+    }
+    distributions.insert(context.to_vec(), distribution.clone());
+    distribution
+}
+
+fn format_log(value: f64) -> String {
+    if value <= LOG_ZERO {
+        String::from("-99")
+    } else {
+        format!("{:.6}", value)
+    }
+}
+
+/// The line number, words, log10 probability, and back-off weight of an n-gram.
+type ParsedNgram<'a> = (usize, Vec<&'a str>, f64, Option<f64>);
+
+/// Parse ARPA text into its n-grams, grouped by length, checking that the counts in the header
+/// match.
+fn parse_arpa(text: &str) -> Result<Vec<Vec<ParsedNgram<'_>>>, FormatError> {
+    let syntax = |line: usize, problem: &str| FormatError::Syntax {
+        line,
+        problem: problem.to_string(),
+    };
+    let parse_number = |field: &str, line: usize| -> Result<f64, FormatError> {
+        field
+            .parse::<f64>()
+            .map_err(|_| syntax(line, &format!("{:?} is not a number", field)))
+    };
+
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    // anything before the "\data\" line is a comment
+    lines
+        .by_ref()
+        .find(|(_, line)| *line == "\\data\\")
+        .ok_or_else(|| syntax(1, "there is no \\data\\ section"))?;
+
+    let mut counts: Vec<usize> = Vec::new();
+    let mut ngrams: Vec<Vec<ParsedNgram>> = Vec::new();
+    let mut current: Option<usize> = None;
+    let mut ended = false;
+    for (number, line) in lines {
+        if line == "\\end\\" {
+            ended = true;
+            break;
+        }
+        if let Some(declaration) = line.strip_prefix("ngram ") {
+            let (n, count): (usize, usize) = declaration
+                .split_once('=')
+                .and_then(|(n, count)| Some((n.trim().parse().ok()?, count.trim().parse().ok()?)))
+                .ok_or_else(|| syntax(number, "expected a line like \"ngram 1=100\""))?;
+            if current.is_some() || n != counts.len() + 1 {
+                return Err(syntax(number, "n-gram counts must come first, in order"));
+            }
+            counts.push(count);
+            continue;
+        }
+        if let Some(n) = line
+            .strip_prefix('\\')
+            .and_then(|header| header.strip_suffix("-grams:"))
+        {
+            let n: usize = n
+                .parse()
+                .map_err(|_| syntax(number, "expected a line like \"\\1-grams:\""))?;
+            if n != ngrams.len() + 1 || n > counts.len() {
+                return Err(syntax(number, "unexpected n-gram section"));
+            }
+            ngrams.push(Vec::new());
+            current = Some(n);
+            continue;
+        }
+        let n = current.ok_or_else(|| syntax(number, "expected an n-gram section"))?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != n + 1 && fields.len() != n + 2 {
+            return Err(syntax(
+                number,
+                &format!(
+                    "expected a {}-gram with a probability and an optional back-off weight",
+                    n
+                ),
+            ));
+        }
+        let log_probability = parse_number(fields[0], number)?;
+        let backoff = match fields.get(n + 1) {
+            Some(field) => Some(parse_number(field, number)?),
+            None => None,
+        };
+        ngrams[n - 1].push((number, fields[1..=n].to_vec(), log_probability, backoff));
+    }
+
+    if !ended {
+        return Err(syntax(text.lines().count(), "there is no \\end\\ line"));
+    }
+    if ngrams.len() != counts.len() {
+        return Err(syntax(text.lines().count(), "an n-gram section is missing"));
+    }
+    for (n, (entries, count)) in ngrams.iter().zip(counts).enumerate() {
+        if entries.len() != count {
+            return Err(FormatError::Corrupt(if n == 0 {
+                "the number of 1-grams doesn't match the header"
+            } else {
+                "the number of n-grams doesn't match the header"
+            }));
+        }
+    }
+    Ok(ngrams)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn char_data() -> Vec<Vec<char>> {
+        vec![
+            vec!['a', 'c', 'e'],
+            vec!['f', 'o', 'o', 'b', 'a', 'r'],
+            vec!['b', 'a', 'z'],
+        ]
+    }
+
+    #[test]
+    fn arpa_round_trip_keeps_the_probabilities() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_prior(0.01)
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .build();
+        let arpa = mm.to_arpa().unwrap();
+        assert!(arpa.contains("\\3-grams:"));
+        assert!(arpa.contains("-99\t<s>\t-99\n"));
+        let imported = MultiMarkov::<char>::from_arpa(&arpa, mm.boundaries.clone()).unwrap();
+        assert_eq!(imported.order, 2);
+        assert_eq!(imported.known_states, mm.known_states);
+        assert_eq!(imported.markov_chain.len(), mm.markov_chain.len());
+        for context in mm.markov_chain.keys() {
+            for state in mm.known_states.iter().chain(Some(&'#')) {
+                let expected = mm.probability_of_next(context, state).unwrap();
+                let actual = imported.probability_of_next(context, state).unwrap();
+                assert!((expected - actual).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn imported_contexts_are_filled_in_from_back_off() {
+        let arpa = "
+\\data\\
+ngram 1=4
+ngram 2=2
+
+\\1-grams:
+-99 <s> -0.30103
+-0.30103 </s>
+-0.60206 the 0.0
+-0.60206 cat
+
+\\2-grams:
+-0.30103 <s> the
+-0.30103 the cat
+
+\\end\\
+";
+        let boundaries = Boundaries {
+            start: String::from("<s>"),
+            end: String::from("</s>"),
+        };
+        let mm = MultiMarkov::<String>::from_arpa(arpa, Some(boundaries)).unwrap();
+        assert_eq!(mm.order, 1);
+        let probability = |context: &str, state: &str| {
+            mm.probability_of_next(&[context.to_string()], &state.to_string())
+                .unwrap()
+        };
+        // "<s> the" is explicit; "<s> cat" backs off to "cat" with a weight of 0.5
+        assert!((probability("<s>", "the") - 0.5 / 0.875).abs() < 1e-6);
+        assert!((probability("<s>", "cat") - 0.125 / 0.875).abs() < 1e-6);
+        // "the" backs off to the unigrams with a weight of 1
+        assert!((probability("the", "the") - 0.25 / 1.25).abs() < 1e-6);
+        assert!(mm.known_states.contains("cat"));
+    }
+
+    #[test]
+    fn bad_arpa_files_are_rejected() {
+        let arpa = "\\data\\\nngram 1=1\nngram 2=1\n\n\\1-grams:\n-0.5 ab\n\n\\2-grams:\n-0.5 ab ab\n\n\\end\\\n";
+        let result = MultiMarkov::<char>::from_arpa(arpa, None);
+        match result {
+            Err(FormatError::Syntax { line, problem }) => {
+                assert_eq!(line, 6);
+                assert_eq!(problem, "\"ab\" is not a valid state");
+            }
+            other => panic!("expected a syntax error, got {:?}", other.map(|_| ())),
+        }
+        assert!(matches!(
+            MultiMarkov::<char>::from_arpa("no data here", None),
+            Err(FormatError::Syntax { .. })
+        ));
+        let mm = MultiMarkov::<String>::builder()
+            .train(vec![vec![String::from("two words"), String::from("x")]].into_iter())
+            .build();
+        assert!(matches!(
+            mm.to_arpa(),
+            Err(FormatError::UnrepresentableState(word)) if word == "two words"
+        ));
+    }
+}
@@ -0,0 +1,581 @@
+//! A compact binary file format for trained models, for when a model is too large or too slow to
+//! save with serde.
+//!
+//! A file starts with the magic header `MMKV` and a format version number, followed by the name of
+//! the state type, the model's settings, a symbol table that stores each distinct state once, the
+//! known states, the transition tables (which refer to states by their index in the symbol table),
+//! and the training set (if any).  It ends with a checksum of everything before it.  All numbers
+//! are little-endian.
+//!
+//! Files written by one version of the crate remain readable by later versions: a new version of
+//! the format gets a new version number, and readers for the older versions are kept.  Files in a
+//! newer version than the crate supports, or claiming version 0 (which never existed), are
+//! rejected with `FormatError::UnsupportedVersion`.
+//!
+//! Version 2 saves the model's prior, and only the observed counts in the transition tables.  In
+//! version 1, the transition tables also contained the prior weights; they are loaded as
+//! observed counts, and the loaded model has no prior.
+
+use crate::error::FormatError;
+use crate::{default_rng, Boundaries, MultiMarkov, SamplingOptions};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::io::{Read, Write};
+
+/// The first bytes of every saved model.
+const MAGIC: &[u8; 4] = b"MMKV";
+
+/// The version of the format that is written, and the newest that can be read.
+pub const FORMAT_VERSION: u16 = 2;
+
+/// A type of state that can be saved in the binary format.  It's implemented for `char`,
+/// `String`, `bool`, and the integer types; implement it to save models of your own state types.
+///
+/// ```
+/// use multimarkov::binary::BinaryState;
+///
+/// #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// struct Note(u8);
+///
+/// impl BinaryState for Note {
+///     const TYPE_NAME: &'static str = "Note";
+///
+///     fn write_state(&self, out: &mut Vec<u8>) {
+///         self.0.write_state(out);
+///     }
+///
+///     fn read_state(input: &mut &[u8]) -> Option<Self> {
+///         u8::read_state(input).map(Note)
+///     }
+/// }
+/// ```
+pub trait BinaryState: Sized {
+    /// A name for the type, saved with the model so that it can't be loaded as a different type.
+    /// Change it if you change how the type is written.
+    const TYPE_NAME: &'static str;
+
+    /// Append the encoding of this state to `out`.
+    fn write_state(&self, out: &mut Vec<u8>);
+
+    /// Decode a state from the start of `input`, and advance `input` past it.  Returns `None` if
+    /// `input` doesn't start with a valid state.
+    fn read_state(input: &mut &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_binary_state_for_integers {
+    ($($t:ty),*) => {
+        $(
+            impl BinaryState for $t {
+                const TYPE_NAME: &'static str = stringify!($t);
+
+                fn write_state(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_state(input: &mut &[u8]) -> Option<Self> {
+                    let bytes = take(input, std::mem::size_of::<$t>())?;
+                    Some(<$t>::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_binary_state_for_integers!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl BinaryState for bool {
+    const TYPE_NAME: &'static str = "bool";
+
+    fn write_state(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn read_state(input: &mut &[u8]) -> Option<Self> {
+        match u8::read_state(input)? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl BinaryState for char {
+    const TYPE_NAME: &'static str = "char";
+
+    fn write_state(&self, out: &mut Vec<u8>) {
+        (*self as u32).write_state(out);
+    }
+
+    fn read_state(input: &mut &[u8]) -> Option<Self> {
+        char::from_u32(u32::read_state(input)?)
+    }
+}
+
+impl BinaryState for String {
+    const TYPE_NAME: &'static str = "String";
+
+    fn write_state(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).write_state(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn read_state(input: &mut &[u8]) -> Option<Self> {
+        let len = u32::read_state(input)? as usize;
+        String::from_utf8(take(input, len)?.to_vec()).ok()
+    }
+}
+
+/// Split the first `len` bytes off `input`.
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if input.len() < len {
+        return None;
+    }
+    let (taken, rest) = input.split_at(len);
+    *input = rest;
+    Some(taken)
+}
+
+/// A 64-bit FNV-1a hash, used as the checksum of a saved model.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl<T> MultiMarkov<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord + BinaryState,
+{
+    /// Save the trained model in the binary format (see the `binary` module).  The RNG is not
+    /// saved.
+    ///
+    /// ```
+    /// use multimarkov::MultiMarkov;
+    /// let mm = MultiMarkov::<char>::builder()
+    ///     .with_boundaries('#', '#')
+    ///     .train(vec![vec!['f', 'o', 'o'], vec!['b', 'a', 'r']].into_iter())
+    ///     .build();
+    /// let mut file: Vec<u8> = Vec::new(); // or a std::fs::File
+    /// mm.write_binary(&mut file).unwrap();
+    /// let loaded = MultiMarkov::<char>::read_binary(file.as_slice()).unwrap();
+    /// assert_eq!(loaded.markov_chain, mm.markov_chain);
+    /// ```
+    pub fn write_binary(&self, mut writer: impl Write) -> Result<(), FormatError> {
+        writer.write_all(&self.to_binary())?;
+        Ok(())
+    }
+
+    /// Load a model saved with `write_binary`.  The loaded model gets a new RNG seeded from the
+    /// thread-local RNG; replace its `rng` field for reproducible output.
+    pub fn read_binary(mut reader: impl Read) -> Result<Self, FormatError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_binary(&bytes)
+    }
+
+    /// The trained model in the binary format, as bytes.
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        FORMAT_VERSION.write_state(&mut out);
+        T::TYPE_NAME.to_string().write_state(&mut out);
+
+        // settings
+        self.order.write_state(&mut out);
+        write_f64(self.sampling.temperature(), &mut out);
+        (self.sampling.top_k().unwrap_or(0) as u64).write_state(&mut out);
+        write_f64(self.sampling.top_p().unwrap_or(0.0), &mut out);
+        write_f64(self.prior.unwrap_or(0.0), &mut out);
+
+        // symbol table: every distinct state, in order
+        let mut symbols: BTreeSet<&T> = self.known_states.iter().collect();
+        for (context, transitions) in &self.markov_chain {
+            symbols.extend(context);
+            symbols.extend(transitions.keys());
+        }
+        if let Some(boundaries) = &self.boundaries {
+            symbols.insert(&boundaries.start);
+            symbols.insert(&boundaries.end);
+        }
+        for sequence in self.training_set.iter().flatten() {
+            symbols.extend(sequence);
+        }
+        (symbols.len() as u32).write_state(&mut out);
+        let mut indices: HashMap<&T, u32> = HashMap::with_capacity(symbols.len());
+        for (index, state) in symbols.into_iter().enumerate() {
+            state.write_state(&mut out);
+            indices.insert(state, index as u32);
+        }
+        let write_index = |state: &T, out: &mut Vec<u8>| indices[state].write_state(out);
+        let write_sequence = |sequence: &[T], out: &mut Vec<u8>| {
+            (sequence.len() as u32).write_state(out);
+            for state in sequence {
+                write_index(state, out);
+            }
+        };
+
+        match &self.boundaries {
+            Some(boundaries) => {
+                true.write_state(&mut out);
+                write_index(&boundaries.start, &mut out);
+                write_index(&boundaries.end, &mut out);
+            }
+            None => false.write_state(&mut out),
+        }
+
+        (self.known_states.len() as u32).write_state(&mut out);
+        for state in &self.known_states {
+            write_index(state, &mut out);
+        }
+
+        // transition tables, sorted by context so that the same model is always saved the same way
+        let mut contexts: Vec<&Vec<T>> = self.markov_chain.keys().collect();
+        contexts.sort();
+        (contexts.len() as u32).write_state(&mut out);
+        for context in contexts {
+            write_sequence(context, &mut out);
+            let transitions = &self.markov_chain[context];
+            (transitions.len() as u32).write_state(&mut out);
+            for (state, weight) in transitions {
+                write_index(state, &mut out);
+                write_f64(*weight, &mut out);
+            }
+        }
+
+        match &self.training_set {
+            Some(training_set) => {
+                true.write_state(&mut out);
+                let mut sequences: Vec<&Vec<T>> = training_set.iter().collect();
+                sequences.sort();
+                (sequences.len() as u32).write_state(&mut out);
+                for sequence in sequences {
+                    write_sequence(sequence, &mut out);
+                }
+            }
+            None => false.write_state(&mut out),
+        }
+
+        checksum(&out).write_state(&mut out);
+        out
+    }
+
+    /// Load a model from bytes in the binary format, as returned by `to_binary`.
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, FormatError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(FormatError::NotAModel);
+        }
+        let mut input = &bytes[MAGIC.len()..];
+        let version = u16::read_state(&mut input).ok_or(FormatError::NotAModel)?;
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(FormatError::UnsupportedVersion { version });
+        }
+        if bytes.len() < MAGIC.len() + 2 + 8 {
+            return Err(FormatError::Corrupt("the data is truncated"));
+        }
+        let (body, saved_checksum) = bytes.split_at(bytes.len() - 8);
+        if checksum(body) != u64::from_le_bytes(saved_checksum.try_into().unwrap()) {
+            return Err(FormatError::ChecksumMismatch);
+        }
+        let mut reader = Reader {
+            version,
+            input: &body[MAGIC.len() + 2..],
+            symbols: Vec::new(),
+        };
+        let model = reader.read_model()?;
+        if !reader.input.is_empty() {
+            return Err(FormatError::Corrupt(
+                "there is unexpected data after the model",
+            ));
+        }
+        Ok(model)
+    }
+}
+
+fn write_f64(value: f64, out: &mut Vec<u8>) {
+    value.to_bits().write_state(out);
+}
+
+/// Decodes the body of a saved model (everything after the version number and before the
+/// checksum), in any version of the format up to the current one.
+struct Reader<'a, T> {
+    version: u16,
+    input: &'a [u8],
+    symbols: Vec<T>,
+}
+
+impl<T> Reader<'_, T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord + BinaryState,
+{
+    fn read<S: BinaryState>(&mut self) -> Result<S, FormatError> {
+        S::read_state(&mut self.input).ok_or(FormatError::Corrupt("the data is truncated"))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, FormatError> {
+        Ok(f64::from_bits(self.read::<u64>()?))
+    }
+
+    fn read_flag(&mut self) -> Result<bool, FormatError> {
+        self.read::<bool>()
+            .map_err(|_| FormatError::Corrupt("a flag is neither true nor false"))
+    }
+
+    fn read_len(&mut self) -> Result<usize, FormatError> {
+        Ok(self.read::<u32>()? as usize)
+    }
+
+    fn read_symbol(&mut self) -> Result<T, FormatError> {
+        let index = self.read_len()?;
+        self.symbols
+            .get(index)
+            .cloned()
+            .ok_or(FormatError::Corrupt("a state index is out of range"))
+    }
+
+    fn read_sequence(&mut self) -> Result<Vec<T>, FormatError> {
+        let len = self.read_len()?;
+        (0..len).map(|_| self.read_symbol()).collect()
+    }
+
+    fn read_model(&mut self) -> Result<MultiMarkov<T>, FormatError> {
+        let type_name: String = self
+            .read()
+            .map_err(|_| FormatError::Corrupt("the state type name is invalid"))?;
+        if type_name != T::TYPE_NAME {
+            return Err(FormatError::StateTypeMismatch {
+                expected: T::TYPE_NAME.to_string(),
+                found: type_name,
+            });
+        }
+
+        let order: i32 = self.read()?;
+        if order < 1 {
+            return Err(FormatError::Corrupt("the order is not positive"));
+        }
+        let sampling = self.read_sampling()?;
+        let prior = match self.version {
+            1 => None,
+            _ => {
+                let prior = self.read_f64()?;
+                if !(prior >= 0.0 && prior.is_finite()) {
+                    return Err(FormatError::Corrupt("the prior is invalid"));
+                }
+                Some(prior).filter(|prior| *prior != 0.0)
+            }
+        };
+
+        let symbol_count = self.read_len()?;
+        let mut symbols = Vec::with_capacity(symbol_count.min(self.input.len()));
+        for _ in 0..symbol_count {
+            let state = T::read_state(&mut self.input).ok_or(FormatError::Corrupt(
+                "a state in the symbol table is invalid",
+            ))?;
+            symbols.push(state);
+        }
+        self.symbols = symbols;
+
+        let boundaries = if self.read_flag()? {
+            Some(Boundaries {
+                start: self.read_symbol()?,
+                end: self.read_symbol()?,
+            })
+        } else {
+            None
+        };
+
+        let known_state_count = self.read_len()?;
+        let known_states = (0..known_state_count)
+            .map(|_| self.read_symbol())
+            .collect::<Result<BTreeSet<T>, FormatError>>()?;
+
+        let context_count = self.read_len()?;
+        let mut markov_chain = HashMap::with_capacity(context_count.min(self.input.len()));
+        for _ in 0..context_count {
+            let context = self.read_sequence()?;
+            let transition_count = self.read_len()?;
+            let mut transitions = BTreeMap::new();
+            for _ in 0..transition_count {
+                let state = self.read_symbol()?;
+                let weight = self.read_f64()?;
+                if !(weight >= 0.0 && weight.is_finite()) {
+                    return Err(FormatError::Corrupt("a transition weight is invalid"));
+                }
+                transitions.insert(state, weight);
+            }
+            markov_chain.insert(context, transitions);
+        }
+
+        let training_set = if self.read_flag()? {
+            let sequence_count = self.read_len()?;
+            Some(
+                (0..sequence_count)
+                    .map(|_| self.read_sequence())
+                    .collect::<Result<HashSet<Vec<T>>, FormatError>>()?,
+            )
+        } else {
+            None
+        };
+
+        Ok(MultiMarkov {
+            markov_chain,
+            prior,
+            known_states,
+            order,
+            boundaries,
+            training_set,
+            sampling,
+            rng: default_rng(),
+        })
+    }
+
+    fn read_sampling(&mut self) -> Result<SamplingOptions, FormatError> {
+        let temperature = self.read_f64()?;
+        let top_k = self.read::<u64>()?;
+        let top_p = self.read_f64()?;
+        if !(temperature > 0.0 && temperature.is_finite()) {
+            return Err(FormatError::Corrupt("the temperature is invalid"));
+        }
+        let mut sampling = SamplingOptions::new().with_temperature(temperature);
+        if top_k > 0 {
+            sampling = sampling.with_top_k(top_k as usize);
+        }
+        if top_p != 0.0 {
+            if !(top_p > 0.0 && top_p <= 1.0) {
+                return Err(FormatError::Corrupt("the top-p setting is invalid"));
+            }
+            sampling = sampling.with_top_p(top_p);
+        }
+        Ok(sampling)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> MultiMarkov<char> {
+        MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .with_training_set()
+            .with_top_k(3)
+            .train(
+                vec![
+                    vec!['a', 'c', 'e'],
+                    vec!['f', 'o', 'o', 'b', 'a', 'r'],
+                    vec!['b', 'a', 'z'],
+                ]
+                .into_iter(),
+            )
+            .build()
+    }
+
+    #[test]
+    fn binary_round_trip_keeps_the_trained_model() {
+        let mm = model();
+        let bytes = mm.to_binary();
+        assert!(bytes.starts_with(b"MMKV\x02\x00"));
+        // the same model is always saved the same way
+        assert_eq!(model().to_binary(), bytes);
+        let loaded = MultiMarkov::<char>::from_binary(&bytes).unwrap();
+        assert_eq!(loaded.markov_chain, mm.markov_chain);
+        assert_eq!(loaded.prior, mm.prior);
+        assert_eq!(loaded.known_states, mm.known_states);
+        assert_eq!(loaded.order, mm.order);
+        assert_eq!(loaded.boundaries, mm.boundaries);
+        assert_eq!(loaded.training_set, mm.training_set);
+        assert_eq!(loaded.sampling, mm.sampling);
+    }
+
+    #[test]
+    fn damaged_or_mismatched_data_is_rejected() {
+        let bytes = model().to_binary();
+        assert!(matches!(
+            MultiMarkov::<char>::from_binary(b"not a model"),
+            Err(FormatError::NotAModel)
+        ));
+        let mut newer = bytes.clone();
+        newer[4] = 3;
+        assert!(matches!(
+            MultiMarkov::<char>::from_binary(&newer),
+            Err(FormatError::UnsupportedVersion { version: 3 })
+        ));
+        let mut unversioned = bytes.clone();
+        unversioned[4] = 0;
+        assert!(matches!(
+            MultiMarkov::<char>::from_binary(&unversioned),
+            Err(FormatError::UnsupportedVersion { version: 0 })
+        ));
+        let mut damaged = bytes.clone();
+        damaged[40] ^= 0xff;
+        assert!(matches!(
+            MultiMarkov::<char>::from_binary(&damaged),
+            Err(FormatError::ChecksumMismatch)
+        ));
+        assert!(matches!(
+            MultiMarkov::<char>::from_binary(&bytes[..bytes.len() - 1]),
+            Err(FormatError::ChecksumMismatch)
+        ));
+        match MultiMarkov::<u32>::from_binary(&bytes) {
+            Err(FormatError::StateTypeMismatch { expected, found }) => {
+                assert_eq!((expected.as_str(), found.as_str()), ("u32", "char"));
+            }
+            other => panic!("expected a type mismatch, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    /// The start of a model of order 1, with the default sampling options and (from version 2)
+    /// no prior.
+    fn header(version: u16) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        version.write_state(&mut out);
+        "char".to_string().write_state(&mut out);
+        1i32.write_state(&mut out);
+        write_f64(1.0, &mut out);
+        0u64.write_state(&mut out);
+        write_f64(0.0, &mut out);
+        if version >= 2 {
+            write_f64(0.0, &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn corrupt_data_with_a_valid_checksum_is_rejected() {
+        let mut out = header(FORMAT_VERSION);
+        1u32.write_state(&mut out); // one symbol...
+        'a'.write_state(&mut out);
+        true.write_state(&mut out);
+        0u32.write_state(&mut out);
+        1u32.write_state(&mut out); // ...but the end boundary refers to a second one
+        checksum(&out).write_state(&mut out);
+        assert!(matches!(
+            MultiMarkov::<char>::from_binary(&out),
+            Err(FormatError::Corrupt("a state index is out of range"))
+        ));
+    }
+
+    #[test]
+    fn version_1_files_can_still_be_read() {
+        let mut out = header(1);
+        2u32.write_state(&mut out);
+        'a'.write_state(&mut out);
+        'b'.write_state(&mut out);
+        false.write_state(&mut out); // no boundaries
+        1u32.write_state(&mut out);
+        1u32.write_state(&mut out); // known states: 'b'
+        1u32.write_state(&mut out);
+        1u32.write_state(&mut out);
+        0u32.write_state(&mut out); // context ['a']...
+        1u32.write_state(&mut out);
+        1u32.write_state(&mut out);
+        write_f64(3.0, &mut out); // ...is followed by 'b' with a weight of 3
+        false.write_state(&mut out); // no training set
+        checksum(&out).write_state(&mut out);
+        let loaded = MultiMarkov::<char>::from_binary(&out).unwrap();
+        assert_eq!(loaded.markov_chain[&vec!['a']][&'b'], 3.0);
+        assert_eq!(loaded.prior, None);
+        assert_eq!(loaded.weight(&['a'], &'a'), None);
+    }
+}
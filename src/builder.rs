@@ -1,9 +1,8 @@
-use crate::MultiMarkov;
-use log::{debug, info};
-use rand::rngs::SmallRng;
-use rand::{thread_rng, Rng, RngCore, SeedableRng};
-use std::cmp::max;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::training::{Trainer, TrainingReport};
+use crate::{check_prior, default_rng, Boundaries, MultiMarkov, MultiMarkovError, SamplingOptions};
+use log::info;
+use rand::RngCore;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::hash::Hash;
 
 pub struct MultiMarkovBuilder<T>
@@ -11,10 +10,15 @@ where
     T: Eq + Hash + Clone + std::cmp::Ord,
 {
     pub markov_chain: HashMap<Vec<T>, BTreeMap<T, f64>>,
-    pub known_states: HashSet<T>,
+    pub known_states: BTreeSet<T>,
     order: i32,
     prior: Option<f64>,
+    boundaries: Option<Boundaries<T>>,
+    training_set: Option<HashSet<Vec<T>>>,
+    sampling: SamplingOptions,
     rng: Box<dyn RngCore + Send + Sync>,
+    training_report: TrainingReport,
+    untraining_report: TrainingReport,
 }
 
 impl<T> MultiMarkovBuilder<T>
@@ -25,10 +29,15 @@ where
     pub fn new() -> Self {
         Self {
             markov_chain: HashMap::new(),
-            known_states: HashSet::new(),
+            known_states: BTreeSet::new(),
             order: MultiMarkov::<T>::DEFAULT_ORDER,
             prior: Some(MultiMarkov::<T>::DEFAULT_PRIOR),
-            rng: Box::new(Box::new(SmallRng::seed_from_u64(thread_rng().gen()))),
+            boundaries: None,
+            training_set: None,
+            sampling: SamplingOptions::new(),
+            rng: default_rng(),
+            training_report: TrainingReport::default(),
+            untraining_report: TrainingReport::default(),
         }
     }
 
@@ -38,12 +47,32 @@ where
     /// slower and require more memory.
     ///
     /// The default is MultiMarkov::DEFAULT_ORDER
+    ///
+    /// Panics if the order isn't positive, or if the builder has already been trained with a
+    /// different order; use `try_with_order` to get an error instead.
     pub fn with_order(mut self, order: i32) -> Self {
         assert!(order > 0, "Order must be an integer greater than zero.");
+        assert!(
+            self.markov_chain.is_empty() || self.order == order,
+            "Order must be set before training."
+        );
         self.order = order;
         self
     }
 
+    /// Like `with_order`, but reports a `MultiMarkovError::InvalidOrder` error if the order isn't
+    /// positive, and a `MultiMarkovError::AlreadyTrained` error if the builder has already been
+    /// trained with a different order.
+    pub fn try_with_order(self, order: i32) -> Result<Self, MultiMarkovError> {
+        if order <= 0 {
+            Err(MultiMarkovError::InvalidOrder { order })
+        } else if !self.markov_chain.is_empty() && self.order != order {
+            Err(MultiMarkovError::AlreadyTrained)
+        } else {
+            Ok(self.with_order(order))
+        }
+    }
+
     /// Specifies the "prior probability" of transition from any known state to any other known state,
     /// if that transition was not observed in the training data.  Small fractions are recommended,
     /// so that this "true randomness" will be less common than transitions based on the training data.
@@ -65,6 +94,63 @@ where
         self
     }
 
+    /// Declares start and end boundary states, which will be added to the beginning and end of
+    /// every training sequence, so that the model learns how sequences begin and end.  Training
+    /// data should *not* already contain them.  The boundary states are not added to the set of
+    /// known states, and the built model remembers them, so that `generate()` starts and stops
+    /// sequences the same way training did.
+    ///
+    /// Panics if the builder has already been trained (with other boundaries, or none): call this
+    /// before `train()`.
+    pub fn with_boundaries(mut self, start: T, end: T) -> Self {
+        let boundaries = Some(Boundaries { start, end });
+        assert!(
+            self.markov_chain.is_empty() || self.boundaries == boundaries,
+            "Boundaries must be set before training."
+        );
+        self.boundaries = boundaries;
+        self
+    }
+
+    /// Keeps a copy of every sequence trained from now on, so that the built model can avoid
+    /// generating sequences that duplicate (or closely resemble) its training data.
+    ///
+    /// Panics if the builder has already been trained without a training set, which would miss
+    /// the sequences trained so far: call this before `train()`.
+    pub fn with_training_set(mut self) -> Self {
+        assert!(
+            self.markov_chain.is_empty() || self.training_set.is_some(),
+            "The training set must be kept from before training."
+        );
+        self.training_set.get_or_insert_with(HashSet::new);
+        self
+    }
+
+    /// Specifies the "temperature" the built model will use for random draws.  Values below 1.0
+    /// make its output more like the training data, and values above 1.0 make it wilder.  It
+    /// can be changed later through the model's `sampling` field.
+    ///
+    /// The default is SamplingOptions::DEFAULT_TEMPERATURE
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.sampling = self.sampling.with_temperature(temperature);
+        self
+    }
+
+    /// Limits the built model's random draws to the `top_k` most likely following states.  It
+    /// can be changed later through the model's `sampling` field.
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.sampling = self.sampling.with_top_k(top_k);
+        self
+    }
+
+    /// Limits the built model's random draws to the smallest set of most likely following states
+    /// whose probabilities add up to at least `top_p`.  It can be changed later through the
+    /// model's `sampling` field.
+    pub fn with_top_p(mut self, top_p: f64) -> Self {
+        self.sampling = self.sampling.with_top_p(top_p);
+        self
+    }
+
     /// Sets a custom Random Number Generator (RNG) for the model.
     pub fn with_rng(mut self, rng: Box<dyn RngCore + Send + Sync>) -> Self {
         self.rng = rng;
@@ -73,96 +159,213 @@ where
 
     /// Ingest an iterator of sequences, adding the observed state transitions to the internal
     /// statistical model.
-    pub fn train(mut self, sequences: impl Iterator<Item = Vec<T>>) -> Self {
-        let mut success_count: usize = 0;
-        let mut error_count: usize = 0;
-        for sequence in sequences {
-            match self.train_sequence(sequence) {
-                Ok(()) => success_count += 1,
-                Err(_) => error_count += 1,
-            };
-        }
-        debug!(
-            "{} sequences successfully trained; {} errors.",
-            success_count, error_count
-        );
+    /// The sequences can be owned (`Vec<T>`) or borrowed (`&Vec<T>`, `&[T]`), for example
+    /// `.train(corpus.iter())`: states and contexts are only cloned into the model the first time
+    /// they are seen, so borrowing saves copying each sequence.
+    pub fn train(mut self, sequences: impl Iterator<Item = impl AsRef<[T]>>) -> Self {
+        let report = self
+            .trainer()
+            .train(sequences.map(|sequence| (sequence, 1.0)));
+        self.training_report.extend(report);
         self
     }
 
-    /// Learn all the transitions possible from one training sequence, adding observations to the Markov model.
-    fn train_sequence(&mut self, sequence: Vec<T>) -> Result<(), &str> {
-        if sequence.len() < 2 {
-            return Err("Sequence was too short, must contain at least two states.");
-        }
+    /// Ingest an iterator of `(sequence, weight)` pairs.  Each transition observed in a sequence
+    /// counts as `weight` observations instead of one, so that some training data (canonical
+    /// names, say, or the words of a frequency list) can count more than the rest.  Weights must
+    /// be positive; sequences with a weight of zero or less (or NaN, or infinity) are rejected.
+    ///
+    /// ```
+    /// use multimarkov::MultiMarkov;
+    /// let mm = MultiMarkov::<char>::builder()
+    ///     .train_weighted(vec![(vec!['a', 'b'], 3.0), (vec!['a', 'c'], 0.5)].into_iter())
+    ///     .build();
+    /// assert_eq!(mm.weight(&['a'], &'b'), Some(3.0));
+    /// ```
+    pub fn train_weighted(
+        mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+    ) -> Self {
+        let report = self.trainer().train(sequences);
+        self.training_report.extend(report);
+        self
+    }
 
-        // loop backwards through the characters in the sequence
-        for i in (1..sequence.len()).rev() {
-            // Build a running set of all known characters while we're at it
-            self.known_states.insert(sequence[i].clone());
-
-            // For the sequences preceding character (i), record that character (i) was observed following them.
-            // IE if the char_vec is ['R','U','S','T'] and this is a 3rd-order model, then for the three models ['S'], ['U','S'], and ['R','U','S'] we record that ['T'] is a known follower.
-            for j in (max(0, i as i32 - self.order) as usize)..i {
-                if let Some(transitions_from) = self.markov_chain.get_mut(&sequence[j..i]) {
-                    // "from" sequence has been seen before
-                    if let Some(weight) = transitions_from.get_mut(&sequence[i]) {
-                        // it has been seen before with this transition; add one observance
-                        *weight += 1.0;
-                    } else {
-                        // it hasn't been seen before with this transition; insert transition with one observance
-                        transitions_from.insert(sequence[i].clone(), 1.0);
-                    }
-                } else {
-                    // "from" sequence hasn't been seen before; add it and add the observed transition
-                    let mut observed_transition = BTreeMap::new();
-                    observed_transition.insert(sequence[i].clone(), 1.0);
-                    self.markov_chain
-                        .insert(Vec::from(&sequence[j..i]), observed_transition);
-                }
-                // The following one-liner might accomplish all of the above, but is pretty hard on the eyes:
-                //     *self.markov_chain.entry(Vec::from(&sequence[j..i])).or_insert(HashMap::new()).entry(sequence[i].clone()).or_insert(0.0) += 1.0;
-            }
-        }
+    /// Strict version of `train`: if any sequence would be rejected, returns a
+    /// `MultiMarkovError::RejectedSequence` error for the first one instead of skipping it, and
+    /// trains none of them.  The builder keeps everything it was trained with before, so it can
+    /// go on with other sequences.  The error's index counts across all of the builder's
+    /// training calls, like the indices in `training_report`.
+    ///
+    /// ```
+    /// use multimarkov::{MultiMarkov, MultiMarkovError};
+    /// let mut builder = MultiMarkov::<char>::builder();
+    /// builder.try_train(vec![vec!['a', 'b']].into_iter()).unwrap();
+    /// assert!(matches!(
+    ///     builder.try_train(vec![vec!['b', 'c'], vec!['c']].into_iter()),
+    ///     Err(MultiMarkovError::RejectedSequence { index: 2, .. })
+    /// ));
+    /// let mm = builder.build();
+    /// ```
+    pub fn try_train(
+        &mut self,
+        sequences: impl Iterator<Item = impl AsRef<[T]>>,
+    ) -> Result<&mut Self, MultiMarkovError> {
+        self.try_train_weighted(sequences.map(|sequence| (sequence, 1.0)))
+    }
 
-        Ok(())
+    /// Strict version of `train_weighted`.  See `try_train`.
+    pub fn try_train_weighted(
+        &mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+    ) -> Result<&mut Self, MultiMarkovError> {
+        let first_index = self.training_report.sequences();
+        let report = self.trainer().try_train(sequences, first_index)?;
+        self.training_report.extend(report);
+        Ok(self)
     }
 
-    /// Adds prior probabilities (if any) and builds the MultiMarkov object.
-    pub fn build(mut self) -> MultiMarkov<T> {
-        self.add_priors();
+    /// What happened to all the sequences given to `train` (and the other training methods) so
+    /// far: how many were trained, which ones were rejected and why, and statistics on their
+    /// lengths and the alphabet.  Rejected sequences are numbered in the order they were given,
+    /// across all calls.
+    pub fn training_report(&self) -> &TrainingReport {
+        &self.training_report
+    }
+
+    /// Remove sequences from the model, subtracting exactly the observations that training them
+    /// added.  Sequences that were never trained are skipped; `untraining_report` tells you
+    /// which.  See `MultiMarkov::untrain`.
+    pub fn untrain(mut self, sequences: impl Iterator<Item = impl AsRef<[T]>>) -> Self {
+        let report = self
+            .trainer()
+            .untrain(sequences.map(|sequence| (sequence, 1.0)));
+        self.untraining_report.extend(report);
+        self
+    }
+
+    /// Remove `(sequence, weight)` pairs from the model, subtracting exactly what training them
+    /// with `train_weighted` added.  See `MultiMarkov::untrain_weighted`.
+    pub fn untrain_weighted(
+        mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+    ) -> Self {
+        let report = self.trainer().untrain(sequences);
+        self.untraining_report.extend(report);
+        self
+    }
+
+    /// Strict version of `untrain`: if any sequence would be rejected, for example because it
+    /// was never trained, returns a `MultiMarkovError::RejectedSequence` error for the first one
+    /// instead of skipping it, and untrains none of them.  Like `try_train`, the builder is kept,
+    /// and the error's index counts across all calls, like the indices in `untraining_report`.
+    pub fn try_untrain(
+        &mut self,
+        sequences: impl Iterator<Item = impl AsRef<[T]>>,
+    ) -> Result<&mut Self, MultiMarkovError> {
+        self.try_untrain_weighted(sequences.map(|sequence| (sequence, 1.0)))
+    }
+
+    /// Strict version of `untrain_weighted`.  See `try_untrain`.
+    pub fn try_untrain_weighted(
+        &mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+    ) -> Result<&mut Self, MultiMarkovError> {
+        let first_index = self.untraining_report.sequences();
+        let report = self.trainer().try_untrain(sequences, first_index)?;
+        self.untraining_report.extend(report);
+        Ok(self)
+    }
+
+    /// What happened to all the sequences given to `untrain` (and the other untraining methods)
+    /// so far: how many were removed, and which ones were rejected and why, for example because
+    /// they were never trained.  Numbered like `training_report`, across all calls.
+    pub fn untraining_report(&self) -> &TrainingReport {
+        &self.untraining_report
+    }
+
+    fn trainer(&mut self) -> Trainer<'_, T> {
+        Trainer {
+            markov_chain: &mut self.markov_chain,
+            known_states: &mut self.known_states,
+            training_set: &mut self.training_set,
+            order: self.order,
+            boundaries: self.boundaries.as_ref(),
+        }
+    }
+
+    /// Builds the MultiMarkov object.  Prior probabilities (if any) are not stored for every
+    /// unobserved transition, but applied implicitly when the model draws or scores states, so
+    /// the model's memory use is proportional to the training data.
+    pub fn build(self) -> MultiMarkov<T> {
+        info!(
+            "Model has {} contexts and {} known states, with a prior of {:?}.",
+            self.markov_chain.len(),
+            self.known_states.len(),
+            self.prior
+        );
         MultiMarkov {
             markov_chain: self.markov_chain,
+            prior: self.prior,
             known_states: self.known_states,
             order: self.order,
+            boundaries: self.boundaries,
+            training_set: self.training_set,
+            sampling: self.sampling,
             rng: self.rng,
         }
     }
 
-    /// Fills in missing state transitions with a given value so that any known state (except
-    /// those only seen at the end of sequences) can transition to any other known state.
-    /// Should be called after training is complete, because only then do we know the full set of
-    /// known states, and which transitions are unobserved.
-    fn add_priors(&mut self) {
-        let mut num_priors_added: usize = 0;
-        match self.prior {
-            Some(p) => {
-                for v in self.markov_chain.values_mut() {
-                    for a in self.known_states.iter() {
-                        v.entry(a.clone()).or_insert_with(|| {
-                            num_priors_added += 1;
-                            p
-                        });
-                    }
-                }
-                info!(
-                    "Model has {} known states and {} trained sequences. {} priors added.",
-                    self.markov_chain.len(),
-                    self.known_states.len(),
-                    num_priors_added
-                );
-            }
-            None => (),
+    /// Like `build`, but reports an error if the model can't be used: a
+    /// `MultiMarkovError::InvalidPrior` if the prior is negative, infinite, or NaN, or a
+    /// `MultiMarkovError::NoTrainingData` if no sequences were trained.
+    ///
+    /// ```
+    /// use multimarkov::{MultiMarkov, MultiMarkovError};
+    /// let result = MultiMarkov::<char>::builder().with_prior(f64::NAN).try_build();
+    /// assert!(matches!(result, Err(MultiMarkovError::InvalidPrior { .. })));
+    /// ```
+    pub fn try_build(self) -> Result<MultiMarkov<T>, MultiMarkovError> {
+        if let Some(prior) = self.prior {
+            check_prior(prior)?;
         }
+        if self.markov_chain.is_empty() {
+            return Err(MultiMarkovError::NoTrainingData);
+        }
+        Ok(self.build())
+    }
+}
+
+impl<T> MultiMarkov<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// Turn a built model back into a builder, with all of its training and settings, for
+    /// example to train more sequences, or to change its prior, sampling options, or RNG before
+    /// building it again.  Its order and boundaries can't change, because the training already
+    /// in the builder depends on them: `with_order` and `with_boundaries` panic if they would.
+    pub fn into_builder(self) -> MultiMarkovBuilder<T> {
+        MultiMarkovBuilder {
+            markov_chain: self.markov_chain,
+            known_states: self.known_states,
+            order: self.order,
+            prior: self.prior,
+            boundaries: self.boundaries,
+            training_set: self.training_set,
+            sampling: self.sampling,
+            rng: self.rng,
+            training_report: TrainingReport::default(),
+            untraining_report: TrainingReport::default(),
+        }
+    }
+}
+
+impl<T> Default for MultiMarkovBuilder<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -209,6 +412,36 @@ mod tests {
             .train(string_data().into_iter());
     }
 
+    #[test]
+    fn invalid_settings_are_reported() {
+        assert!(matches!(
+            MultiMarkov::<char>::builder().try_with_order(0),
+            Err(MultiMarkovError::InvalidOrder { order: 0 })
+        ));
+        for prior in [-0.5, f64::INFINITY, f64::NAN] {
+            assert!(matches!(
+                MultiMarkov::<char>::builder()
+                    .with_prior(prior)
+                    .train(char_data().into_iter())
+                    .try_build(),
+                Err(MultiMarkovError::InvalidPrior { .. })
+            ));
+        }
+        assert!(matches!(
+            MultiMarkov::<char>::builder()
+                .train(vec![vec!['a']].into_iter())
+                .try_build(),
+            Err(MultiMarkovError::NoTrainingData)
+        ));
+        let mm = MultiMarkov::<char>::builder()
+            .try_with_order(2)
+            .unwrap()
+            .train(char_data().into_iter())
+            .try_build()
+            .unwrap();
+        assert_eq!(mm.order, 2);
+    }
+
     #[test]
     fn sequences_in_training_show_up_in_model() {
         let mm = MultiMarkov::<char>::builder()
@@ -244,11 +477,11 @@ mod tests {
             .with_prior(0.015)
             .build();
         // prior should be set for a non-observed transition such as 'a' -> 'b'
-        assert!(mm.markov_chain.get(&*vec!['a']).unwrap().contains_key(&'b'));
-        assert_eq!(
-            *mm.markov_chain.get(&*vec!['a']).unwrap().get(&'b').unwrap(),
-            0.015
-        );
+        assert_eq!(mm.weight(&['a'], &'b'), Some(0.015));
+        // ...but kept apart from the observed transitions
+        assert!(!mm.markov_chain.get(&*vec!['a']).unwrap().contains_key(&'b'));
+        assert_eq!(mm.weight(&['a'], &'c'), Some(1.0));
+        assert_eq!(mm.prior, Some(0.015));
     }
 
     #[test]
@@ -259,18 +492,9 @@ mod tests {
             .with_prior(0.011)
             .build();
         // prior should be set for a non-observed transition such as 'a' -> 'b'
-        assert!(mm
-            .markov_chain
-            .get(&*vec![String::from("a")])
-            .unwrap()
-            .contains_key(&String::from("b")));
         assert_eq!(
-            *mm.markov_chain
-                .get(&*vec![String::from("a")])
-                .unwrap()
-                .get(&String::from("b"))
-                .unwrap(),
-            0.011
+            mm.weight(&[String::from("a")], &String::from("b")),
+            Some(0.011)
         );
     }
 
@@ -283,6 +507,65 @@ mod tests {
             .build();
         // a non-observed transition such as 'a' -> 'b' should have no entry in the model
         assert!(!mm.markov_chain.get(&*vec!['a']).unwrap().contains_key(&'b'));
+        assert_eq!(mm.weight(&['a'], &'b'), None);
+    }
+
+    #[test]
+    fn boundaries_are_added_to_training_sequences() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('<', '>')
+            .train(char_data().into_iter())
+            .without_prior()
+            .build();
+        // the single-state sequence ['a'] can be trained once it's wrapped in boundaries
+        assert_eq!(
+            *mm.markov_chain.get(&*vec!['<']).unwrap().get(&'a').unwrap(),
+            2.0
+        );
+        assert!(mm
+            .markov_chain
+            .get(&*vec!['<', 'f'])
+            .unwrap()
+            .contains_key(&'o'));
+        assert!(mm
+            .markov_chain
+            .get(&*vec!['a', 'z'])
+            .unwrap()
+            .contains_key(&'>'));
+        // boundary states are not part of the alphabet
+        assert!(!mm.known_states.contains(&'<'));
+        assert!(!mm.known_states.contains(&'>'));
+    }
+
+    #[test]
+    fn priors_include_the_end_boundary() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_boundaries('<', '>')
+            .train(char_data().into_iter())
+            .with_prior(0.015)
+            .build();
+        // 'f' was never seen at the end of a sequence, but has a small chance of ending one
+        assert_eq!(mm.weight(&['f'], &'>'), Some(0.015));
+        // the start boundary state can never be drawn
+        assert_eq!(mm.weight(&['f'], &'<'), None);
+    }
+
+    #[test]
+    fn training_set_is_kept_only_on_request() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .build();
+        assert!(mm.training_set.is_none());
+        let mm = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .with_training_set()
+            .train(char_data().into_iter())
+            .build();
+        let training_set = mm.training_set.unwrap();
+        assert_eq!(training_set.len(), 4);
+        assert!(training_set.contains(&vec!['b', 'a', 'z'])); // stored without boundaries
     }
 
     #[test]
@@ -293,6 +576,53 @@ mod tests {
             .train(char_data().into_iter());
     }
 
+    #[test]
+    #[should_panic(expected = "Boundaries must be set before training.")]
+    fn boundaries_cannot_be_added_after_training() {
+        let _builder = MultiMarkov::<char>::builder()
+            .train(char_data().into_iter())
+            .with_boundaries('#', '#');
+    }
+
+    #[test]
+    #[should_panic(expected = "The training set must be kept from before training.")]
+    fn training_set_cannot_be_started_after_training() {
+        let _builder = MultiMarkov::<char>::builder()
+            .train(char_data().into_iter())
+            .with_training_set();
+    }
+
+    #[test]
+    fn rebuilding_may_repeat_the_same_settings() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .with_training_set()
+            .train(char_data().into_iter())
+            .build()
+            .into_builder()
+            .with_boundaries('#', '#')
+            .with_training_set()
+            .build();
+        assert!(mm.boundaries.is_some());
+    }
+
+    #[test]
+    fn order_cannot_change_after_training() {
+        let builder = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .train(char_data().into_iter())
+            .build()
+            .into_builder();
+        assert!(matches!(
+            builder.try_with_order(3),
+            Err(MultiMarkovError::AlreadyTrained)
+        ));
+        let builder = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .train(char_data().into_iter());
+        assert_eq!(builder.try_with_order(1).unwrap().build().order, 1);
+    }
+
     #[test]
     fn test_that_seeded_rngs_give_the_same_output_every_time() {
         use rand::{rngs::SmallRng, SeedableRng};
@@ -306,15 +636,15 @@ mod tests {
             .train(char_data().into_iter())
             .without_prior()
             .build();
-        assert_eq!(mm1.random_next(&vec!['a']), mm2.random_next(&vec!['a']));
-        assert_eq!(mm1.random_next(&vec!['a']), mm2.random_next(&vec!['a']));
-        assert_eq!(mm1.random_next(&vec!['a']), mm2.random_next(&vec!['a']));
-        assert_eq!(mm1.random_next(&vec!['a']), mm2.random_next(&vec!['a']));
-        assert_eq!(mm1.random_next(&vec!['a']), mm2.random_next(&vec!['a']));
-        assert_eq!(mm1.random_next(&vec!['a']), mm2.random_next(&vec!['a']));
-        assert_eq!(mm1.random_next(&vec!['a']), mm2.random_next(&vec!['a']));
-        assert_eq!(mm1.random_next(&vec!['a']), mm2.random_next(&vec!['a']));
-        assert_eq!(mm1.random_next(&vec!['a']), mm2.random_next(&vec!['a']));
-        assert_eq!(mm1.random_next(&vec!['a']), mm2.random_next(&vec!['a']));
+        assert_eq!(mm1.random_next(&['a']), mm2.random_next(&['a']));
+        assert_eq!(mm1.random_next(&['a']), mm2.random_next(&['a']));
+        assert_eq!(mm1.random_next(&['a']), mm2.random_next(&['a']));
+        assert_eq!(mm1.random_next(&['a']), mm2.random_next(&['a']));
+        assert_eq!(mm1.random_next(&['a']), mm2.random_next(&['a']));
+        assert_eq!(mm1.random_next(&['a']), mm2.random_next(&['a']));
+        assert_eq!(mm1.random_next(&['a']), mm2.random_next(&['a']));
+        assert_eq!(mm1.random_next(&['a']), mm2.random_next(&['a']));
+        assert_eq!(mm1.random_next(&['a']), mm2.random_next(&['a']));
+        assert_eq!(mm1.random_next(&['a']), mm2.random_next(&['a']));
     }
 }
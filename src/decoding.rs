@@ -0,0 +1,206 @@
+use crate::MultiMarkov;
+use std::hash::Hash;
+
+impl<T> MultiMarkov<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// The single most probable state to follow the given sequence, with its log-probability
+    /// (natural logarithm).  Uses the same back-off as `random_next`, but with the trained
+    /// weights as they are, without the model's `sampling` options.  Ties go to the state that
+    /// comes first in order.  Returns `None` if there is no model for the sequence.
+    pub fn most_likely_next(&self, current_sequence: &[T]) -> Option<(T, f64)> {
+        let (_, transitions) = self.best_model(current_sequence)?;
+        let sum_of_weights = transitions.sum();
+        let mut best: Option<(&T, f64)> = None;
+        for (k, v) in transitions.iter() {
+            if best.is_none_or(|(_, best_weight)| v > best_weight) {
+                best = Some((k, v));
+            }
+        }
+        best.map(|(k, v)| (k.clone(), (v / sum_of_weights).ln()))
+    }
+
+    /// Greedily decode the most probable continuation of the given sequence, by repeatedly
+    /// appending the `most_likely_next` state until the end boundary state comes up (if the model
+    /// has boundaries) or `max_length` states have been added.  Returns the continuation, without
+    /// the end state, and its log-probability (including the transition to the end state, if it
+    /// was reached).  Returns `None` if there is no model for the sequence, or if decoding runs
+    /// into a state with no model before it can stop (only possible in a model without
+    /// boundaries, at a state that was only seen at the end of training sequences): a
+    /// continuation is only returned if it stopped at the end state or at `max_length`.
+    ///
+    /// To decode a whole sequence, start from the start boundary state:
+    ///
+    /// ```
+    /// use multimarkov::MultiMarkov;
+    /// let mm = MultiMarkov::<char>::builder()
+    ///     .with_boundaries('#', '#')
+    ///     .train(vec![vec!['f', 'o', 'o'], vec!['b', 'a', 'r']].into_iter())
+    ///     .build();
+    /// let (sequence, log_probability) = mm.greedy_decode(&['#'], 10).unwrap();
+    /// ```
+    pub fn greedy_decode(
+        &self,
+        current_sequence: &[T],
+        max_length: usize,
+    ) -> Option<(Vec<T>, f64)> {
+        let end = self.boundaries.as_ref().map(|b| &b.end);
+        let mut sequence = current_sequence.to_vec();
+        let mut log_probability = 0.0;
+        for _ in 0..max_length {
+            let (next, next_log_probability) = self.most_likely_next(&sequence)?;
+            log_probability += next_log_probability;
+            if Some(&next) == end {
+                break;
+            }
+            sequence.push(next);
+        }
+        Some((sequence.split_off(current_sequence.len()), log_probability))
+    }
+
+    /// Find the `beam_width` most probable continuations of the given sequence with a beam
+    /// search, keeping only the `beam_width` best partial continuations at each step.  If the model
+    /// has boundaries, only complete continuations (those that reach the end state within
+    /// `max_length` states) are returned; otherwise, continuations of `max_length` states are.
+    /// Each continuation is returned without the end state, with its log-probability, most
+    /// probable first.  A `beam_width` of zero finds nothing.
+    pub fn beam_search(
+        &self,
+        current_sequence: &[T],
+        beam_width: usize,
+        max_length: usize,
+    ) -> Vec<(Vec<T>, f64)> {
+        if beam_width == 0 {
+            return Vec::new();
+        }
+        let end = self.boundaries.as_ref().map(|b| &b.end);
+        let mut beams: Vec<(Vec<T>, f64)> = vec![(current_sequence.to_vec(), 0.0)];
+        let mut completed: Vec<(Vec<T>, f64)> = Vec::new();
+
+        while !beams.is_empty() {
+            // log-probabilities only go down, so stop once no beam can beat the completed ones
+            if completed.len() >= beam_width && beams[0].1 < completed[beam_width - 1].1 {
+                break;
+            }
+            let mut candidates: Vec<(Vec<T>, f64)> = Vec::new();
+            for (sequence, log_probability) in beams {
+                let length = sequence.len() - current_sequence.len();
+                if length == max_length {
+                    if end.is_none() {
+                        completed.push((sequence, log_probability));
+                    }
+                    continue;
+                }
+                let (_, transitions) = match self.best_model(&sequence) {
+                    Some(found) => found,
+                    None => continue,
+                };
+                let sum_of_weights = transitions.sum();
+                for (k, v) in transitions.iter() {
+                    let next_log_probability = log_probability + (v / sum_of_weights).ln();
+                    if Some(k) == end {
+                        completed.push((sequence.clone(), next_log_probability));
+                    } else {
+                        let mut next = sequence.clone();
+                        next.push(k.clone());
+                        candidates.push((next, next_log_probability));
+                    }
+                }
+            }
+            sort_by_log_probability(&mut candidates);
+            candidates.truncate(beam_width);
+            sort_by_log_probability(&mut completed);
+            completed.truncate(beam_width);
+            beams = candidates;
+        }
+
+        completed
+            .into_iter()
+            .map(|(mut sequence, log_probability)| {
+                (sequence.split_off(current_sequence.len()), log_probability)
+            })
+            .collect()
+    }
+}
+
+/// Most probable first; the sort is stable, so ties keep their order.
+fn sort_by_log_probability<T>(sequences: &mut [(Vec<T>, f64)]) {
+    sequences.sort_by(|a, b| b.1.total_cmp(&a.1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn char_data() -> Vec<Vec<char>> {
+        vec![
+            vec!['a', 'c', 'e'],
+            vec!['f', 'o', 'o', 'b', 'a', 'r'],
+            vec!['b', 'a', 'z'],
+        ]
+    }
+
+    fn model() -> MultiMarkov<char> {
+        MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .without_prior()
+            .build()
+    }
+
+    #[test]
+    fn most_likely_next_breaks_ties_in_order() {
+        let mm = model();
+        // 'a', 'b', and 'f' are equally likely to start a sequence
+        let (next, log_probability) = mm.most_likely_next(&['#']).unwrap();
+        assert_eq!(next, 'a');
+        assert!((log_probability - (1.0f64 / 3.0).ln()).abs() < 1e-12);
+        assert!(mm.most_likely_next(&['x']).is_none());
+    }
+
+    #[test]
+    fn greedy_decoding_follows_the_most_likely_path() {
+        let mm = model();
+        let (sequence, log_probability) = mm.greedy_decode(&['#'], 10).unwrap();
+        assert_eq!(sequence, vec!['a', 'c', 'e']);
+        assert!((log_probability - (1.0f64 / 3.0).ln()).abs() < 1e-12);
+        // stops at the maximum length
+        let (sequence, _) = mm.greedy_decode(&['#'], 2).unwrap();
+        assert_eq!(sequence, vec!['a', 'c']);
+    }
+
+    #[test]
+    fn greedy_decoding_fails_at_a_dead_end() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .without_prior()
+            .train(char_data().into_iter())
+            .build();
+        // 'a' is followed by 'c', which is followed by 'e', which was never followed by anything
+        assert_eq!(mm.greedy_decode(&['a'], 2).unwrap().0, vec!['c', 'e']);
+        assert_eq!(mm.greedy_decode(&['a'], 3), None);
+    }
+
+    #[test]
+    fn beam_search_finds_the_most_probable_sequences() {
+        let mm = model();
+        let results = mm.beam_search(&['#'], 3, 10);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, vec!['a', 'c', 'e']);
+        assert!((results[0].1 - (1.0f64 / 3.0).ln()).abs() < 1e-12);
+        // "ba" is followed by 'r' or 'z', so the next-best sequences have probability 1/6
+        for (sequence, log_probability) in &results[1..] {
+            assert!(sequence.ends_with(&['a', 'r']) || sequence.ends_with(&['a', 'z']));
+            assert!((log_probability - (1.0f64 / 6.0).ln()).abs() < 1e-12);
+        }
+        // no sequence of at most two states can be completed
+        assert!(mm.beam_search(&['#'], 3, 2).is_empty());
+    }
+
+    #[test]
+    fn beam_search_with_no_width_finds_nothing() {
+        assert!(model().beam_search(&['#'], 0, 10).is_empty());
+    }
+}
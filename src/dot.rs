@@ -0,0 +1,264 @@
+use crate::MultiMarkov;
+use std::cmp::min;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::{self, Write as _};
+use std::hash::Hash;
+use std::io::{self, Write};
+
+/// Options that limit which part of the transition graph `MultiMarkov::to_dot` exports.  Set
+/// them up with the builder-style `with_*` methods:
+///
+/// ```
+/// use multimarkov::DotOptions;
+/// let options = DotOptions::<char>::new()
+///     .with_max_order(2)
+///     .with_min_weight(2.0)
+///     .with_root(vec!['x'])
+///     .without_prior_edges();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotOptions<T> {
+    max_order: Option<usize>,
+    min_weight: f64,
+    root: Option<Vec<T>>,
+    prior_edges: bool,
+}
+
+impl<T> DotOptions<T> {
+    /// Options that export the whole graph.
+    pub fn new() -> Self {
+        Self {
+            max_order: None,
+            min_weight: 0.0,
+            root: None,
+            prior_edges: true,
+        }
+    }
+
+    /// Only export contexts of at most this many states.  Transitions lead to the longest
+    /// context within this limit that the model would back off to.
+    pub fn with_max_order(mut self, max_order: usize) -> Self {
+        self.max_order = Some(max_order);
+        self
+    }
+
+    /// Only export transitions with at least this weight.
+    pub fn with_min_weight(mut self, min_weight: f64) -> Self {
+        self.min_weight = min_weight;
+        self
+    }
+
+    /// Only export the part of the graph that can be reached from this context, by following
+    /// the exported transitions.
+    pub fn with_root(mut self, root: Vec<T>) -> Self {
+        self.root = Some(root);
+        self
+    }
+
+    /// Hide transitions that were never observed in training, and only have a prior weight.
+    pub fn without_prior_edges(mut self) -> Self {
+        self.prior_edges = false;
+        self
+    }
+}
+
+impl<T> Default for DotOptions<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where a transition leads: a context in the graph, or a state that has no following states
+/// (such as the end boundary).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Node<'a, T> {
+    Context(&'a [T]),
+    Terminal(&'a T),
+}
+
+/// A transition: the drawn state, its weight, whether the weight is a prior, and where it leads.
+type Edge<'a, T> = (&'a T, f64, bool, Node<'a, T>);
+
+impl<T> MultiMarkov<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord + fmt::Display,
+{
+    /// Render the contexts in `markov_chain` and the weighted transitions between them as a
+    /// Graphviz DOT graph, for example to see why the model keeps producing some combination of
+    /// states.  Each context is a node, labeled with its states.  Each transition is an edge,
+    /// labeled with the drawn state and its weight, that leads to the context the model would use
+    /// to draw the next state.  States that nothing follows, such as the end boundary state, are
+    /// drawn as boxes, and transitions that only have a prior weight are dashed.  Render the
+    /// result with `dot -Tsvg model.dot > model.svg`.
+    ///
+    /// ```
+    /// use multimarkov::{DotOptions, MultiMarkov};
+    /// let mm = MultiMarkov::<char>::builder()
+    ///     .with_order(2)
+    ///     .with_boundaries('#', '#')
+    ///     .without_prior()
+    ///     .train(vec![vec!['f', 'o', 'o'], vec!['b', 'a', 'r']].into_iter())
+    ///     .build();
+    /// let dot = mm.to_dot(&DotOptions::new().with_max_order(1));
+    /// assert!(dot.contains("label=\"o (1)\""));
+    /// ```
+    pub fn to_dot(&self, options: &DotOptions<T>) -> String {
+        let max_order = min(
+            options.max_order.unwrap_or(self.order as usize),
+            self.order as usize,
+        );
+        let end = self.boundaries.as_ref().map(|b| &b.end);
+        let mut edges: BTreeMap<Node<T>, Vec<Edge<T>>> = BTreeMap::new();
+        let mut exported: BTreeSet<Node<T>> = BTreeSet::new();
+        let mut queue: VecDeque<Node<T>> = VecDeque::new();
+        match &options.root {
+            Some(root) => queue.extend(self.dot_context(root, max_order)),
+            None => queue.extend(
+                self.markov_chain
+                    .keys()
+                    .filter(|context| context.len() <= max_order)
+                    .map(|context| Node::Context(context.as_slice())),
+            ),
+        }
+
+        while let Some(node) = queue.pop_front() {
+            if !exported.insert(node.clone()) {
+                continue;
+            }
+            let context = match node {
+                Node::Context(context) => context,
+                Node::Terminal(_) => continue,
+            };
+            let mut outgoing = Vec::new();
+            let transitions = self.transitions(&self.markov_chain[context]);
+            for (state, weight) in transitions.iter() {
+                let prior = !transitions.observed.contains_key(state);
+                if weight < options.min_weight || (prior && !options.prior_edges) {
+                    continue;
+                }
+                let mut next: Vec<T> = context.to_vec();
+                next.push(state.clone());
+                let target = match self.dot_context(&next, max_order) {
+                    Some(target) if Some(state) != end => target,
+                    _ => Node::Terminal(state),
+                };
+                queue.push_back(target.clone());
+                outgoing.push((state, weight, prior, target));
+            }
+            edges.insert(node, outgoing);
+        }
+
+        let ids: BTreeMap<&Node<T>, usize> = exported
+            .iter()
+            .enumerate()
+            .map(|(id, node)| (node, id))
+            .collect();
+        let mut dot = String::from("digraph multimarkov {\n");
+        for (node, id) in &ids {
+            match node {
+                Node::Context(context) => {
+                    let label: Vec<String> = context.iter().map(|s| s.to_string()).collect();
+                    writeln!(dot, "    n{} [label=\"{}\"];", id, escape(&label.join(" ")))
+                }
+                Node::Terminal(state) => writeln!(
+                    dot,
+                    "    n{} [label=\"{}\", shape=box];",
+                    id,
+                    escape(&state.to_string())
+                ),
+            }
+            .unwrap();
+        }
+        for (node, outgoing) in &edges {
+            for (state, weight, prior, target) in outgoing {
+                writeln!(
+                    dot,
+                    "    n{} -> n{} [label=\"{} ({})\"{}];",
+                    ids[node],
+                    ids[target],
+                    escape(&state.to_string()),
+                    weight,
+                    if *prior { ", style=dashed" } else { "" }
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Write the graph from `to_dot` to a file or stream.
+    pub fn write_dot(&self, mut writer: impl Write, options: &DotOptions<T>) -> io::Result<()> {
+        writer.write_all(self.to_dot(options).as_bytes())
+    }
+
+    /// The longest context of at most `max_order` states at the end of the sequence, that the
+    /// model has a model for.
+    fn dot_context<'a>(&'a self, sequence: &[T], max_order: usize) -> Option<Node<'a, T>> {
+        (1..=min(max_order, sequence.len())).rev().find_map(|i| {
+            self.markov_chain
+                .get_key_value(&sequence[sequence.len() - i..])
+                .map(|(context, _)| Node::Context(context.as_slice()))
+        })
+    }
+}
+
+/// Escape a label for a double-quoted DOT string.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> MultiMarkov<char> {
+        MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .with_prior(0.5)
+            .train(vec![vec!['a', 'b'], vec!['b', 'a', 'b']].into_iter())
+            .build()
+    }
+
+    #[test]
+    fn dot_export_follows_the_back_off() {
+        let mm = model();
+        let dot = mm.to_dot(&DotOptions::new().without_prior_edges());
+        assert!(dot.starts_with("digraph multimarkov {\n"));
+        // contexts #, a, b, # a, # b, a b, b a, and the end state
+        assert_eq!(
+            dot.matches("[label=").count() - dot.matches(" -> ").count(),
+            8
+        );
+        assert_eq!(dot.matches("shape=box").count(), 1);
+        // "a b" is followed by the end state twice; "# a" by 'b' once, leading to "a b"
+        assert!(dot.contains("[label=\"# (2)\"]"));
+        assert!(dot.contains("[label=\"b (1)\"]"));
+        assert!(!dot.contains("(0.5)"));
+        // with priors, every context can be followed by every known state and the end state
+        let with_priors = mm.to_dot(&DotOptions::new());
+        assert_eq!(with_priors.matches(" -> ").count(), 7 * 3);
+        assert_eq!(with_priors.matches("style=dashed").count(), 7 * 3 - 9);
+    }
+
+    #[test]
+    fn dot_export_can_be_limited() {
+        let mm = model();
+        // only single-state contexts, and the end state
+        let dot = mm.to_dot(&DotOptions::new().with_max_order(1).with_min_weight(1.0));
+        assert_eq!(
+            dot.matches("[label=").count() - dot.matches(" -> ").count(),
+            4
+        );
+        assert!(!dot.contains("# a"));
+        // only what can be reached from "a": "a" -> "a b" -> end, with observed transitions only
+        let dot = mm.to_dot(&DotOptions::new().with_root(vec!['a']).with_min_weight(1.0));
+        assert_eq!(
+            dot,
+            "digraph multimarkov {\n    n0 [label=\"a\"];\n    n1 [label=\"a b\"];\n    \
+             n2 [label=\"#\", shape=box];\n    n0 -> n1 [label=\"b (2)\"];\n    \
+             n1 -> n2 [label=\"# (2)\"];\n}\n"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::training::RejectionReason;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Errors that can be reported by the MultiMarkov crate.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum MultiMarkovError {
+    /// The order of the model must be a positive integer.
+    InvalidOrder { order: i32 },
+    /// The builder has already been trained, so its order can no longer change: the contexts it
+    /// has trained depend on it.
+    AlreadyTrained,
+    /// The prior must be a finite number, zero or greater.
+    InvalidPrior { prior: f64 },
+    /// The model was built without any training data, so it has nothing to draw states from.
+    NoTrainingData,
+    /// The model was built without start/end boundary states, so it doesn't know where a
+    /// sequence begins or ends.  Use `with_boundaries()` on the builder.
+    NoBoundaries,
+    /// No trained model was found for any tail-end subsequence of the current sequence, so no
+    /// following state could be drawn.
+    NoContext,
+    /// Generation was asked to avoid duplicating the training data, but the model was built
+    /// without a training set.  Use `with_training_set()` on the builder.
+    NoTrainingSet,
+    /// The requested minimum length of generated sequences is greater than the maximum length.
+    InvalidLengthRange {
+        min_length: usize,
+        max_length: usize,
+    },
+    /// The model cannot generate any sequence that satisfies the generation options, for
+    /// example because the prefix and suffix are longer than the maximum length, or because the
+    /// suffix can never be drawn.
+    Unsatisfiable,
+    /// Every attempt to generate a sequence that satisfies the generation options failed.  Counts
+    /// how many attempts ended too short (the only possible next state was the end state), too
+    /// long (the end state could not be drawn at the maximum length), and too similar to a
+    /// training sequence.
+    AttemptsExhausted {
+        attempts: usize,
+        too_short: usize,
+        too_long: usize,
+        not_novel: usize,
+    },
+    /// Strict training (see `MultiMarkovBuilder::try_train`) was given a sequence that can't be
+    /// trained.  Gives its index, counting from zero, and the reason.  A builder counts the
+    /// sequences across all of its calls, like its `training_report`; a built model counts them
+    /// within the one call, like the `TrainingReport` it returns.
+    RejectedSequence {
+        index: usize,
+        reason: RejectionReason,
+    },
+}
+
+impl fmt::Display for MultiMarkovError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultiMarkovError::InvalidOrder { order } => {
+                write!(f, "order {} is not a positive integer", order)
+            }
+            MultiMarkovError::AlreadyTrained => {
+                write!(f, "the order can't change after training")
+            }
+            MultiMarkovError::InvalidPrior { prior } => {
+                write!(f, "prior {} is not a finite number, zero or greater", prior)
+            }
+            MultiMarkovError::NoTrainingData => write!(f, "the model has no training data"),
+            MultiMarkovError::NoBoundaries => {
+                write!(f, "the model has no start/end boundary states")
+            }
+            MultiMarkovError::NoContext => {
+                write!(f, "no trained model matches the current sequence")
+            }
+            MultiMarkovError::NoTrainingSet => {
+                write!(f, "the model was built without a training set")
+            }
+            MultiMarkovError::InvalidLengthRange {
+                min_length,
+                max_length,
+            } => write!(
+                f,
+                "minimum length {} is greater than maximum length {}",
+                min_length, max_length
+            ),
+            MultiMarkovError::Unsatisfiable => {
+                write!(f, "no sequence can satisfy the generation options")
+            }
+            MultiMarkovError::AttemptsExhausted {
+                attempts,
+                too_short,
+                too_long,
+                not_novel,
+            } => write!(
+                f,
+                "gave up after {} attempts ({} too short, {} too long, {} not novel)",
+                attempts, too_short, too_long, not_novel
+            ),
+            MultiMarkovError::RejectedSequence { index, reason } => {
+                write!(f, "training sequence {} was rejected: {}", index, reason)
+            }
+        }
+    }
+}
+
+impl Error for MultiMarkovError {}
+
+/// Errors that can be reported when reading or writing a saved model, in the binary format (see
+/// `MultiMarkov::write_binary`) or the ARPA format (see `MultiMarkov::write_arpa`).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FormatError {
+    /// Reading or writing the underlying file or stream failed.
+    Io(io::Error),
+    /// The data doesn't start with the binary format's magic header, so it isn't a saved model.
+    NotAModel,
+    /// The model was saved in a newer version of the format than this version of the crate can
+    /// read, or claims a version of the format that never existed.
+    UnsupportedVersion { version: u16 },
+    /// The model was saved with a different type of state than the one it is being loaded as,
+    /// for example as `MultiMarkov<String>` but loaded as `MultiMarkov<char>`.
+    StateTypeMismatch { expected: String, found: String },
+    /// The data doesn't match the checksum it was saved with, so it was damaged after saving.
+    ChecksumMismatch,
+    /// The data is truncated or contains invalid values.  Describes what was wrong.
+    Corrupt(&'static str),
+    /// A line of a text format can't be parsed.  Gives the line number (starting at 1) and
+    /// describes what was wrong.
+    Syntax { line: usize, problem: String },
+    /// A state can't be written in a text format, for example because it contains whitespace.
+    UnrepresentableState(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(error) => write!(f, "I/O error: {}", error),
+            FormatError::NotAModel => write!(f, "not a saved MultiMarkov model"),
+            FormatError::UnsupportedVersion { version } => write!(
+                f,
+                "the model was saved in format version {}, which this crate doesn't support",
+                version
+            ),
+            FormatError::StateTypeMismatch { expected, found } => write!(
+                f,
+                "the model has states of type {}, not {}",
+                found, expected
+            ),
+            FormatError::ChecksumMismatch => write!(f, "the model's checksum doesn't match"),
+            FormatError::Corrupt(problem) => write!(f, "the model is corrupt: {}", problem),
+            FormatError::Syntax { line, problem } => {
+                write!(f, "syntax error on line {}: {}", line, problem)
+            }
+            FormatError::UnrepresentableState(state) => {
+                write!(f, "the state {:?} can't be written in this format", state)
+            }
+        }
+    }
+}
+
+impl Error for FormatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FormatError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FormatError {
+    fn from(error: io::Error) -> Self {
+        FormatError::Io(error)
+    }
+}
@@ -0,0 +1,206 @@
+use crate::MultiMarkov;
+use std::fmt;
+use std::hash::Hash;
+use std::slice;
+
+/// The results of scoring a held-out set of sequences with `MultiMarkov::evaluate`.  Use it to
+/// compare models trained with different settings: lower perplexity is better.
+///
+/// Sequences that contain out-of-vocabulary states (states the model never saw in training) are
+/// counted but not scored, since the model can't assign them a meaningful probability.  Sequences
+/// that can't be scored because no trained model matches some part of them, or because they have
+/// no transitions to score, are listed separately.  Neither kind counts towards the perplexity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvaluationReport {
+    /// The number of held-out sequences.
+    pub sequences: usize,
+    /// The number of sequences that were scored.
+    pub scored_sequences: usize,
+    /// The number of state transitions in the scored sequences (including transitions from the
+    /// start state and to the end state, if the model has boundaries).
+    pub scored_transitions: usize,
+    /// The sum of the log-likelihoods of the scored sequences.
+    pub total_log_likelihood: f64,
+    /// The number of occurrences of out-of-vocabulary states.
+    pub oov_states: usize,
+    /// The indices of the sequences that contain out-of-vocabulary states.
+    pub oov_sequences: Vec<usize>,
+    /// The indices of the sequences for which no trained model matched some part of the sequence,
+    /// or that have no transitions (a single state, in a model without boundaries).
+    pub unscorable_sequences: Vec<usize>,
+}
+
+impl EvaluationReport {
+    /// The perplexity of the model on the scored sequences, per state transition: the
+    /// exponential of the negative average log-likelihood of a transition.  It can be read as the
+    /// number of states the model is effectively choosing between at each step.  Returns `None`
+    /// if nothing was scored.
+    pub fn perplexity(&self) -> Option<f64> {
+        if self.scored_transitions == 0 {
+            return None;
+        }
+        Some((-self.total_log_likelihood / self.scored_transitions as f64).exp())
+    }
+
+    /// The average log-likelihood of a scored sequence.  Returns `None` if nothing was scored.
+    pub fn average_log_likelihood(&self) -> Option<f64> {
+        if self.scored_sequences == 0 {
+            return None;
+        }
+        Some(self.total_log_likelihood / self.scored_sequences as f64)
+    }
+}
+
+impl fmt::Display for EvaluationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} sequences scored",
+            self.scored_sequences, self.sequences
+        )?;
+        if let (Some(perplexity), Some(average)) =
+            (self.perplexity(), self.average_log_likelihood())
+        {
+            write!(
+                f,
+                " (perplexity {:.4}, average log-likelihood {:.4})",
+                perplexity, average
+            )?;
+        }
+        write!(
+            f,
+            "; {} out-of-vocabulary states in {} sequences; {} sequences unscorable",
+            self.oov_states,
+            self.oov_sequences.len(),
+            self.unscorable_sequences.len()
+        )
+    }
+}
+
+impl<T> MultiMarkov<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// Score a held-out set of sequences (sequences that were *not* used in training) with
+    /// `log_likelihood`, and report how well the model predicts them.
+    pub fn evaluate<S: AsRef<[T]>>(
+        &self,
+        sequences: impl IntoIterator<Item = S>,
+    ) -> EvaluationReport {
+        let mut report = EvaluationReport {
+            sequences: 0,
+            scored_sequences: 0,
+            scored_transitions: 0,
+            total_log_likelihood: 0.0,
+            oov_states: 0,
+            oov_sequences: Vec::new(),
+            unscorable_sequences: Vec::new(),
+        };
+        for (index, sequence) in sequences.into_iter().enumerate() {
+            let sequence = sequence.as_ref();
+            report.sequences += 1;
+            // without boundaries, the first state is taken as given, and only needs to have been
+            // seen at the start of a training sequence, where it isn't counted as known
+            let (first, predicted) = match (self.boundaries.as_ref(), sequence.split_first()) {
+                (None, Some((first, rest))) => (Some(first), rest),
+                _ => (None, sequence),
+            };
+            let oov_states = first
+                .filter(|first| !self.markov_chain.contains_key(slice::from_ref(*first)))
+                .into_iter()
+                .chain(predicted)
+                .filter(|state| !self.known_states.contains(*state))
+                .count();
+            if oov_states > 0 {
+                report.oov_states += oov_states;
+                report.oov_sequences.push(index);
+                continue;
+            }
+            match self.log_likelihood(sequence) {
+                Some(log_likelihood) => {
+                    report.scored_sequences += 1;
+                    report.total_log_likelihood += log_likelihood;
+                    report.scored_transitions += match self.boundaries {
+                        Some(_) => sequence.len() + 1,
+                        None => sequence.len().saturating_sub(1),
+                    };
+                }
+                None => report.unscorable_sequences.push(index),
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn char_data() -> Vec<Vec<char>> {
+        vec![
+            vec!['a', 'c', 'e'],
+            vec!['f', 'o', 'o', 'b', 'a', 'r'],
+            vec!['b', 'a', 'z'],
+        ]
+    }
+
+    #[test]
+    fn evaluation_reports_perplexity() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .without_prior()
+            .build();
+        let report = mm.evaluate(vec![vec!['a', 'c', 'e'], vec!['b', 'a', 'r']]);
+        assert_eq!(report.sequences, 2);
+        assert_eq!(report.scored_sequences, 2);
+        assert_eq!(report.scored_transitions, 8);
+        // "ace" has probability 1/3, and "bar" has probability 1/6
+        let expected = (1.0f64 / 3.0).ln() + (1.0f64 / 6.0).ln();
+        assert!((report.total_log_likelihood - expected).abs() < 1e-12);
+        assert!((report.perplexity().unwrap() - (-expected / 8.0).exp()).abs() < 1e-12);
+        assert!((report.average_log_likelihood().unwrap() - expected / 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn evaluation_reports_what_could_not_be_scored() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .train(char_data().into_iter())
+            .build();
+        // 'x' and 'y' are out of vocabulary, and 'z' is known but never followed by anything
+        let held_out: Vec<&[char]> = vec![&['a', 'c'], &['a', 'x', 'y'], &['z', 'a'], &['f', 'o']];
+        let report = mm.evaluate(held_out);
+        assert_eq!(report.scored_sequences, 2);
+        assert_eq!(report.oov_states, 2);
+        assert_eq!(report.oov_sequences, vec![1]);
+        assert_eq!(report.unscorable_sequences, vec![2]);
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "2 of 4 sequences scored (perplexity {:.4}, average log-likelihood {:.4}); \
+                 2 out-of-vocabulary states in 1 sequences; 1 sequences unscorable",
+                report.perplexity().unwrap(),
+                report.average_log_likelihood().unwrap()
+            )
+        );
+        assert!(mm.evaluate(Vec::<Vec<char>>::new()).perplexity().is_none());
+    }
+
+    #[test]
+    fn single_states_without_boundaries_are_not_scored() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .train(char_data().into_iter())
+            .build();
+        // 'q' was never seen, 'a' has no transition to score, and 'f' was seen as a first state
+        let held_out: Vec<&[char]> = vec![&['q'], &['a'], &['f', 'o'], &['q', 'a']];
+        let report = mm.evaluate(held_out);
+        assert_eq!(report.scored_sequences, 1);
+        assert_eq!(report.oov_states, 2);
+        assert_eq!(report.oov_sequences, vec![0, 3]);
+        assert_eq!(report.unscorable_sequences, vec![1]);
+        assert_eq!(mm.log_likelihood(&['a']), None);
+    }
+}
@@ -0,0 +1,893 @@
+use crate::{weighted_choice, Boundaries, MultiMarkov, MultiMarkovError, SamplingOptions};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::iter::once;
+
+/// Without a maximum length, suffix-constrained generation plans for at most this many states
+/// between the prefix and the suffix.
+const SUFFIX_HORIZON: usize = 20;
+
+/// Without a maximum length, an attempt at generating a sequence gives up (as too long) after
+/// this many states.  Otherwise a model that keeps cycling without drawing the end state, for
+/// example because top-k or top-p truncation or a very low temperature cut it off, would never
+/// finish.
+const LENGTH_HORIZON: usize = 1000;
+
+/// Options that constrain the sequences produced by `MultiMarkov::generate_with`.  Set them up
+/// with the builder-style `with_*` methods:
+///
+/// ```
+/// use multimarkov::GenerationOptions;
+/// let options = GenerationOptions::<char>::new()
+///     .with_prefix(vec!['m', 'a', 'r'])
+///     .with_suffix(vec!['i', 'u', 's'])
+///     .with_min_length(4)
+///     .with_max_length(10)
+///     .with_min_edit_distance(1)
+///     .with_temperature(1.5)
+///     .with_top_p(0.95)
+///     .with_max_attempts(50);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationOptions<T> {
+    prefix: Vec<T>,
+    suffix: Vec<T>,
+    min_length: usize,
+    max_length: Option<usize>,
+    min_edit_distance: usize,
+    temperature: Option<f64>,
+    top_k: Option<usize>,
+    top_p: Option<f64>,
+    max_attempts: usize,
+}
+
+impl<T> GenerationOptions<T> {
+    pub const DEFAULT_MAX_ATTEMPTS: usize = 100;
+
+    /// Options with no constraints.
+    pub fn new() -> Self {
+        Self {
+            prefix: Vec::new(),
+            suffix: Vec::new(),
+            min_length: 0,
+            max_length: None,
+            min_edit_distance: 0,
+            temperature: None,
+            top_k: None,
+            top_p: None,
+            max_attempts: Self::DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+
+    /// Generated sequences will begin with these states.  They are used as the initial context
+    /// (after the start state) for drawing the rest of the sequence.
+    pub fn with_prefix(mut self, prefix: Vec<T>) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Generated sequences will end with these states.  Instead of generating sequences and
+    /// rejecting those with the wrong ending, each state is drawn in proportion to its
+    /// probability times the probability that the model can still reach the suffix (followed by
+    /// the end state) from there, within the length limits.  If no maximum length is set, at most
+    /// 20 states are generated between the prefix and the suffix.
+    ///
+    /// The look-ahead is planned again on every call to `generate_with`, for every context the
+    /// model can reach at every length up to the maximum.  With priors, that is nearly every
+    /// context, so the cost grows with the size of the model times the maximum length: for a
+    /// model of a few thousand names, tens to hundreds of milliseconds per call.  Keep the
+    /// maximum length as small as the use allows.
+    pub fn with_suffix(mut self, suffix: Vec<T>) -> Self {
+        self.suffix = suffix;
+        self
+    }
+
+    /// Generated sequences will contain at least this many states (not counting boundaries, but
+    /// counting any prefix and suffix).
+    /// Until the minimum is reached, the end state is left out of each random draw.
+    pub fn with_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Generated sequences will contain at most this many states (not counting boundaries, but
+    /// counting any prefix and suffix).
+    /// Once the maximum is reached, the end state is drawn if it was observed to follow the
+    /// sequence in training (a prior weight isn't enough, or names would be cut off mid-word);
+    /// otherwise the attempt fails as too long.
+    /// Without a maximum length, an attempt gives up after 1000 states.
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Generated sequences must differ from every sequence in the model's training set by at
+    /// least this many insertions, deletions, or substitutions (the Levenshtein edit distance).
+    /// Use `1` to reject only exact copies of training data.  Requires that the model was built
+    /// with `with_training_set()`.
+    pub fn with_min_edit_distance(mut self, min_edit_distance: usize) -> Self {
+        self.min_edit_distance = min_edit_distance;
+        self
+    }
+
+    /// Overrides the temperature of the model's sampling options for these draws.  See
+    /// `SamplingOptions::with_temperature`.
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(
+            SamplingOptions::new()
+                .with_temperature(temperature)
+                .temperature(),
+        );
+        self
+    }
+
+    /// Overrides the top-k truncation of the model's sampling options for these draws.  See
+    /// `SamplingOptions::with_top_k`.
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = SamplingOptions::new().with_top_k(top_k).top_k();
+        self
+    }
+
+    /// Overrides the top-p truncation of the model's sampling options for these draws.  See
+    /// `SamplingOptions::with_top_p`.
+    pub fn with_top_p(mut self, top_p: f64) -> Self {
+        self.top_p = SamplingOptions::new().with_top_p(top_p).top_p();
+        self
+    }
+
+    /// How many times to start over, if a sequence can't satisfy the constraints, before giving
+    /// up with an error.
+    ///
+    /// The default is GenerationOptions::DEFAULT_MAX_ATTEMPTS
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+}
+
+impl<T> GenerationOptions<T> {
+    /// The model's sampling options, with any overrides from these options.
+    fn sampling(&self, model_sampling: &SamplingOptions) -> SamplingOptions {
+        let mut sampling = *model_sampling;
+        if let Some(temperature) = self.temperature {
+            sampling = sampling.with_temperature(temperature);
+        }
+        if let Some(top_k) = self.top_k {
+            sampling = sampling.with_top_k(top_k);
+        }
+        if let Some(top_p) = self.top_p {
+            sampling = sampling.with_top_p(top_p);
+        }
+        sampling
+    }
+}
+
+impl<T> Default for GenerationOptions<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The outcome of one attempt at generating a sequence.
+enum Attempt<T> {
+    Generated(Vec<T>),
+    TooShort,
+    TooLong,
+}
+
+impl<T> MultiMarkov<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// Generate a complete new sequence.  Starting from the start boundary state, states are
+    /// drawn with `random_next` until the end boundary state is drawn.  The boundary states
+    /// themselves are not included in the returned sequence.
+    ///
+    /// Requires that the model was built with `with_boundaries()`.
+    pub fn generate(&mut self) -> Result<Vec<T>, MultiMarkovError> {
+        self.generate_with(&GenerationOptions::default())
+    }
+
+    /// Generate a complete new sequence that satisfies the given options.  Rather than only
+    /// rejecting sequences of the wrong length after the fact, generation is steered towards
+    /// the end state: it can't be drawn before the minimum length, and it is forced at the
+    /// maximum length.  An attempt fails if the model leaves no other choice, or if the result is
+    /// too similar to the training data; after `max_attempts` failures, an error reports why.
+    pub fn generate_with(
+        &mut self,
+        options: &GenerationOptions<T>,
+    ) -> Result<Vec<T>, MultiMarkovError> {
+        self.with_own_rng(|mm, rng| mm.generate_with_rng(options, rng))
+    }
+
+    /// Like `generate_with`, but draws from the given random-number generator instead of the
+    /// model's own, so it only needs a shared reference to the model.  This way one trained model
+    /// can be shared between threads and sampled concurrently without locks:
+    ///
+    /// ```
+    /// use multimarkov::{GenerationOptions, MultiMarkov};
+    /// use rand::{rngs::SmallRng, SeedableRng};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// let mm = Arc::new(
+    ///     MultiMarkov::<char>::builder()
+    ///         .with_boundaries('#', '#')
+    ///         .train(vec![vec!['f', 'o', 'o'], vec!['b', 'a', 'r']].into_iter())
+    ///         .build(),
+    /// );
+    /// let workers: Vec<_> = (0..4)
+    ///     .map(|seed| {
+    ///         let mm = Arc::clone(&mm);
+    ///         thread::spawn(move || {
+    ///             let mut rng = SmallRng::seed_from_u64(seed);
+    ///             mm.generate_with_rng(&GenerationOptions::new(), &mut rng)
+    ///         })
+    ///     })
+    ///     .collect();
+    /// for worker in workers {
+    ///     assert!(worker.join().unwrap().is_ok());
+    /// }
+    /// ```
+    pub fn generate_with_rng(
+        &self,
+        options: &GenerationOptions<T>,
+        rng: &mut impl Rng,
+    ) -> Result<Vec<T>, MultiMarkovError> {
+        let boundaries = self
+            .boundaries
+            .clone()
+            .ok_or(MultiMarkovError::NoBoundaries)?;
+        if let Some(max_length) = options.max_length {
+            if max_length < options.min_length {
+                return Err(MultiMarkovError::InvalidLengthRange {
+                    min_length: options.min_length,
+                    max_length,
+                });
+            }
+        }
+        if options.min_edit_distance > 0 && self.training_set.is_none() {
+            return Err(MultiMarkovError::NoTrainingSet);
+        }
+
+        let sampling = options.sampling(&self.sampling);
+        let mut planner = None;
+        if !options.suffix.is_empty() {
+            let fixed_length = options.prefix.len() + options.suffix.len();
+            let max_length = options.max_length.unwrap_or(fixed_length + SUFFIX_HORIZON);
+            let mut suffix_planner = SuffixPlanner {
+                suffix: &options.suffix,
+                end: &boundaries.end,
+                min_length: options.min_length,
+                max_length,
+                sampling,
+                completions: HashMap::new(),
+            };
+            let start = self.initial_sequence(&boundaries, options);
+            let (context, _) = self.best_model(&start).ok_or(MultiMarkovError::NoContext)?;
+            suffix_planner.plan(self, context, options.prefix.len());
+            if suffix_planner.completion(context, options.prefix.len()) <= 0.0 {
+                return Err(MultiMarkovError::Unsatisfiable);
+            }
+            planner = Some(suffix_planner);
+        } else if options
+            .max_length
+            .is_some_and(|max| options.prefix.len() > max)
+        {
+            return Err(MultiMarkovError::Unsatisfiable);
+        }
+
+        let mut too_short: usize = 0;
+        let mut too_long: usize = 0;
+        let mut not_novel: usize = 0;
+        for _ in 0..options.max_attempts {
+            let attempt = match &mut planner {
+                Some(planner) => {
+                    self.attempt_suffix_generation(&boundaries, options, planner, rng)?
+                }
+                None => self.attempt_generation(&boundaries, options, &sampling, rng)?,
+            };
+            match attempt {
+                Attempt::Generated(sequence) if self.is_novel(&sequence, options) => {
+                    return Ok(sequence)
+                }
+                Attempt::Generated(_) => not_novel += 1,
+                Attempt::TooShort => too_short += 1,
+                Attempt::TooLong => too_long += 1,
+            }
+        }
+        Err(MultiMarkovError::AttemptsExhausted {
+            attempts: options.max_attempts,
+            too_short,
+            too_long,
+            not_novel,
+        })
+    }
+
+    /// Checks that a generated sequence is at least `min_edit_distance` away from every sequence
+    /// in the training set.
+    fn is_novel(&self, sequence: &[T], options: &GenerationOptions<T>) -> bool {
+        let training_set = match &self.training_set {
+            Some(training_set) => training_set,
+            None => return true,
+        };
+        match options.min_edit_distance {
+            0 => true,
+            1 => !training_set.contains(sequence),
+            d => training_set.iter().all(|trained| {
+                // the difference in lengths is a lower bound on the edit distance
+                trained.len().abs_diff(sequence.len()) >= d || edit_distance(trained, sequence) >= d
+            }),
+        }
+    }
+
+    /// The start state followed by the prefix, if any.
+    fn initial_sequence(
+        &self,
+        boundaries: &Boundaries<T>,
+        options: &GenerationOptions<T>,
+    ) -> Vec<T> {
+        let mut sequence = Vec::with_capacity(options.prefix.len() + 1);
+        sequence.push(boundaries.start.clone());
+        sequence.extend(options.prefix.iter().cloned());
+        sequence
+    }
+
+    fn attempt_generation(
+        &self,
+        boundaries: &Boundaries<T>,
+        options: &GenerationOptions<T>,
+        sampling: &SamplingOptions,
+        rng: &mut impl Rng,
+    ) -> Result<Attempt<T>, MultiMarkovError> {
+        let mut sequence = self.initial_sequence(boundaries, options);
+        loop {
+            let length = sequence.len() - 1;
+            if options.max_length.is_none() && length >= LENGTH_HORIZON {
+                return Ok(Attempt::TooLong);
+            }
+            let r: f64 = rng.random();
+            let (context, choices) = self
+                .sampling_distribution(&sequence, sampling)
+                .ok_or(MultiMarkovError::NoContext)?;
+            let next = if options.max_length.is_some_and(|max| length >= max) {
+                // only end where training did, not wherever the prior allows it
+                if self.markov_chain[context].contains_key(&boundaries.end)
+                    && choices
+                        .iter()
+                        .any(|(k, v)| **k == boundaries.end && *v > 0.0)
+                {
+                    &boundaries.end
+                } else {
+                    return Ok(Attempt::TooLong);
+                }
+            } else if length < options.min_length {
+                let choices = choices
+                    .iter()
+                    .filter(|(k, _)| **k != boundaries.end)
+                    .copied();
+                match weighted_choice(choices, r) {
+                    Some(next) => next,
+                    None => return Ok(Attempt::TooShort),
+                }
+            } else {
+                weighted_choice(choices.into_iter(), r).ok_or(MultiMarkovError::NoContext)?
+            };
+            if *next == boundaries.end {
+                sequence.remove(0);
+                return Ok(Attempt::Generated(sequence));
+            }
+            sequence.push(next.clone());
+        }
+    }
+
+    fn attempt_suffix_generation(
+        &self,
+        boundaries: &Boundaries<T>,
+        options: &GenerationOptions<T>,
+        planner: &mut SuffixPlanner<T>,
+        rng: &mut impl Rng,
+    ) -> Result<Attempt<T>, MultiMarkovError> {
+        let mut sequence = self.initial_sequence(boundaries, options);
+        loop {
+            let length = sequence.len() - 1;
+            let r: f64 = rng.random();
+            let (context, distribution) = self
+                .sampling_distribution(&sequence, &planner.sampling)
+                .ok_or(MultiMarkovError::NoContext)?;
+            let sum_of_weights: f64 = distribution.iter().map(|(_, v)| v).sum();
+            // `None` stands for finishing the sequence with the suffix right now
+            let mut choices = vec![(None, planner.finish(self, context, length))];
+            for &(state, weight) in distribution.iter().filter(|(k, _)| **k != boundaries.end) {
+                let mut next = context.clone();
+                next.push(state.clone());
+                if let Some((next_context, _)) = self.best_model(&next) {
+                    let completion = planner.completion(next_context, length + 1);
+                    choices.push((Some(state), weight / sum_of_weights * completion));
+                }
+            }
+            match weighted_choice(choices.into_iter(), r) {
+                Some(Some(state)) => sequence.push(state.clone()),
+                Some(None) => {
+                    sequence.extend(options.suffix.iter().cloned());
+                    sequence.remove(0);
+                    return Ok(Attempt::Generated(sequence));
+                }
+                // only reachable through rounding errors, since the planner looked ahead
+                None => return Ok(Attempt::TooLong),
+            }
+        }
+    }
+
+    /// The probability that the model draws `state` to follow the given sequence, with the given
+    /// sampling options.
+    fn transition_probability(
+        &self,
+        current_sequence: &[T],
+        state: &T,
+        sampling: &SamplingOptions,
+    ) -> f64 {
+        match self.sampling_distribution(current_sequence, sampling) {
+            Some((_, distribution)) => {
+                let sum_of_weights: f64 = distribution.iter().map(|(_, v)| v).sum();
+                match distribution.iter().find(|(k, _)| *k == state) {
+                    Some((_, weight)) => weight / sum_of_weights,
+                    None => 0.0,
+                }
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// Looks ahead for suffix-constrained generation.  From a context (the key of a model in the
+/// Markov chain) and the length of the sequence so far, `completion` is the probability that the
+/// model goes on to draw the suffix and the end state within the length limits, possibly after
+/// some other states.  Drawing each state in proportion to its probability times the completion
+/// probability that follows it samples from the model's sequences that end with the suffix.
+struct SuffixPlanner<'a, T> {
+    suffix: &'a [T],
+    end: &'a T,
+    min_length: usize,
+    max_length: usize,
+    sampling: SamplingOptions,
+    completions: HashMap<(&'a [T], usize), f64>,
+}
+
+/// The contexts a model can reach at one length, with the contexts that can follow each of them
+/// and the probability of getting there.
+type Layer<'a, T> = HashMap<&'a [T], Vec<(&'a [T], f64)>>;
+
+impl<'a, T> SuffixPlanner<'a, T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// The probability that the model draws the suffix and then the end state right away.
+    fn finish(&self, model: &MultiMarkov<T>, context: &[T], length: usize) -> f64 {
+        let final_length = length + self.suffix.len();
+        if final_length < self.min_length || final_length > self.max_length {
+            return 0.0;
+        }
+        let mut sequence = context.to_vec();
+        let mut probability = 1.0;
+        for state in self.suffix.iter().chain(once(self.end)) {
+            probability *= model.transition_probability(&sequence, state, &self.sampling);
+            if probability == 0.0 {
+                break;
+            }
+            sequence.push(state.clone());
+        }
+        probability
+    }
+
+    /// Work out the completion probability of every context the model can reach from `start`,
+    /// at every length from `length` up to the maximum.  First the contexts reachable at each
+    /// length are found, going forward; then the table is filled in from the maximum length
+    /// downward, since each completion depends on the completions one state later.
+    fn plan(&mut self, model: &'a MultiMarkov<T>, start: &'a [T], length: usize) {
+        let mut layers: Vec<Layer<'a, T>> = Vec::new();
+        let mut frontier: HashSet<&'a [T]> = once(start).collect();
+        while !frontier.is_empty() {
+            let layer_length = length + layers.len();
+            let mut layer = HashMap::new();
+            let mut next_frontier = HashSet::new();
+            for context in frontier {
+                let mut successors = Vec::new();
+                if layer_length + self.suffix.len() < self.max_length {
+                    if let Some((_, distribution)) =
+                        model.sampling_distribution(context, &self.sampling)
+                    {
+                        let sum_of_weights: f64 = distribution.iter().map(|(_, v)| v).sum();
+                        for &(state, weight) in distribution.iter().filter(|(k, _)| *k != self.end)
+                        {
+                            let mut next = context.to_vec();
+                            next.push(state.clone());
+                            if let Some((next_context, _)) = model.best_model(&next) {
+                                next_frontier.insert(next_context.as_slice());
+                                successors.push((next_context.as_slice(), weight / sum_of_weights));
+                            }
+                        }
+                    }
+                }
+                layer.insert(context, successors);
+            }
+            layers.push(layer);
+            frontier = next_frontier;
+        }
+
+        for (i, layer) in layers.into_iter().enumerate().rev() {
+            let layer_length = length + i;
+            for (context, successors) in layer {
+                let mut completion = self.finish(model, context, layer_length);
+                for (next_context, probability) in successors {
+                    completion += probability * self.completion(next_context, layer_length + 1);
+                }
+                self.completions.insert((context, layer_length), completion);
+            }
+        }
+    }
+
+    /// The completion probability of a context at a length, as worked out by `plan`.
+    fn completion(&self, context: &[T], length: usize) -> f64 {
+        self.completions
+            .get(&(context, length))
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+/// The Levenshtein edit distance between two sequences: the number of single-state insertions,
+/// deletions, or substitutions needed to turn one into the other.
+fn edit_distance<T: Eq>(a: &[T], b: &[T]) -> usize {
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+    let mut current_row = vec![0; b.len() + 1];
+    for (i, x) in a.iter().enumerate() {
+        current_row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous_row[j] + if x == y { 0 } else { 1 };
+            current_row[j + 1] = substitution
+                .min(previous_row[j + 1] + 1)
+                .min(current_row[j] + 1);
+        }
+        std::mem::swap(&mut previous_row, &mut current_row);
+    }
+    previous_row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+    use std::collections::HashSet;
+
+    fn char_data() -> Vec<Vec<char>> {
+        vec![
+            vec!['a', 'c', 'e'],
+            vec!['f', 'o', 'o', 'b', 'a', 'r'],
+            vec!['b', 'a', 'z'],
+        ]
+    }
+
+    #[test]
+    fn shared_models_draw_from_the_given_rng() {
+        let builder = || {
+            MultiMarkov::<char>::builder()
+                .with_order(2)
+                .with_boundaries('#', '#')
+                .train(char_data().into_iter())
+        };
+        let mut own = builder()
+            .with_rng(Box::new(SmallRng::seed_from_u64(99)))
+            .build();
+        let shared = builder().build();
+        let mut rng = SmallRng::seed_from_u64(99);
+        let options = GenerationOptions::new().with_max_length(8);
+        for _ in 0..10 {
+            assert_eq!(
+                shared.generate_with_rng(&options, &mut rng),
+                own.generate_with(&options)
+            );
+        }
+        assert_eq!(
+            shared.random_next_with_rng(&['#', 'b'], &mut rng),
+            own.random_next(&['#', 'b'])
+        );
+        assert_eq!(
+            shared.try_random_next_with_rng(&['q'], &mut rng),
+            Err(MultiMarkovError::NoContext)
+        );
+    }
+
+    #[test]
+    fn suffix_planning_does_not_recurse() {
+        // a small stack, like a worker thread's, would overflow if planning recursed once per state
+        let worker = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(|| {
+                let mut mm = MultiMarkov::<char>::builder()
+                    .with_order(1)
+                    .with_boundaries('#', '#')
+                    .with_rng(Box::new(SmallRng::seed_from_u64(1234)))
+                    .train(vec![vec!['a', 'b'], vec!['b', 'a']].into_iter())
+                    .build();
+                let options = GenerationOptions::new()
+                    .with_suffix(vec!['b'])
+                    .with_max_length(10_000);
+                mm.generate_with(&options)
+            })
+            .unwrap();
+        let sequence = worker.join().unwrap().unwrap();
+        assert_eq!(sequence.last(), Some(&'b'));
+    }
+
+    #[test]
+    fn sequences_only_end_at_the_maximum_length_where_training_did() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .with_boundaries('#', '#')
+            .with_prior(0.5)
+            .train(vec![vec!['a', 'b']].into_iter())
+            .build();
+        // the end state only has a prior weight after 'a'
+        let options = GenerationOptions::new()
+            .with_prefix(vec!['a'])
+            .with_max_length(1)
+            .with_max_attempts(3);
+        assert_eq!(
+            mm.generate_with(&options),
+            Err(MultiMarkovError::AttemptsExhausted {
+                attempts: 3,
+                too_short: 0,
+                too_long: 3,
+                not_novel: 0
+            })
+        );
+        let options = options.with_prefix(vec!['a', 'b']).with_max_length(2);
+        assert_eq!(mm.generate_with(&options), Ok(vec!['a', 'b']));
+    }
+
+    #[test]
+    fn endless_cycles_are_cut_off() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .train(vec![vec!['a', 'b', 'a', 'b', 'a', 'b', 'a', 'b']].into_iter())
+            .build();
+        // the most likely state to follow "a b" is always 'a', never the end state
+        let options = GenerationOptions::new().with_top_k(1).with_max_attempts(3);
+        assert_eq!(
+            mm.generate_with(&options),
+            Err(MultiMarkovError::AttemptsExhausted {
+                attempts: 3,
+                too_short: 0,
+                too_long: 3,
+                not_novel: 0
+            })
+        );
+    }
+
+    #[test]
+    fn generated_sequences_are_stripped_of_boundaries() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .with_rng(Box::new(SmallRng::seed_from_u64(1234)))
+            .train(char_data().into_iter())
+            .without_prior()
+            .build();
+        for _ in 0..20 {
+            let sequence = mm.generate().unwrap();
+            assert!(!sequence.contains(&'#'));
+        }
+    }
+
+    #[test]
+    fn generate_requires_boundaries() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .train(char_data().into_iter())
+            .build();
+        assert_eq!(mm.generate(), Err(MultiMarkovError::NoBoundaries));
+    }
+
+    #[test]
+    fn generate_reports_missing_context() {
+        // the model was trained without boundaries, so there's no model for the start state
+        let mut mm = MultiMarkov::<char>::builder()
+            .train(char_data().into_iter())
+            .build();
+        mm.boundaries = Some(crate::Boundaries {
+            start: '<',
+            end: '>',
+        });
+        assert_eq!(mm.generate(), Err(MultiMarkovError::NoContext));
+    }
+
+    #[test]
+    fn generated_sequences_respect_length_limits() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .with_rng(Box::new(SmallRng::seed_from_u64(1234)))
+            .train(char_data().into_iter())
+            .build();
+        let options = GenerationOptions::new()
+            .with_min_length(4)
+            .with_max_length(5);
+        for _ in 0..20 {
+            let sequence = mm.generate_with(&options).unwrap();
+            assert!(sequence.len() >= 4 && sequence.len() <= 5);
+        }
+    }
+
+    #[test]
+    fn generation_gives_up_and_says_why() {
+        // without priors, 'z' can only be followed by the end state, so "baz" can't be extended
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .train(vec![vec!['b', 'a', 'z']].into_iter())
+            .without_prior()
+            .build();
+        let options = GenerationOptions::new()
+            .with_min_length(4)
+            .with_max_attempts(3);
+        assert_eq!(
+            mm.generate_with(&options),
+            Err(MultiMarkovError::AttemptsExhausted {
+                attempts: 3,
+                too_short: 3,
+                too_long: 0,
+                not_novel: 0,
+            })
+        );
+        let options = GenerationOptions::new()
+            .with_max_length(2)
+            .with_max_attempts(3);
+        assert_eq!(
+            mm.generate_with(&options),
+            Err(MultiMarkovError::AttemptsExhausted {
+                attempts: 3,
+                too_short: 0,
+                too_long: 3,
+                not_novel: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn min_length_cannot_exceed_max_length() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .build();
+        let options = GenerationOptions::new()
+            .with_min_length(6)
+            .with_max_length(5);
+        assert_eq!(
+            mm.generate_with(&options),
+            Err(MultiMarkovError::InvalidLengthRange {
+                min_length: 6,
+                max_length: 5,
+            })
+        );
+    }
+
+    #[test]
+    fn novel_generation_avoids_training_data() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .with_training_set()
+            .with_rng(Box::new(SmallRng::seed_from_u64(1234)))
+            .train(char_data().into_iter())
+            .build();
+        let training_set = mm.training_set.clone().unwrap();
+        let options = GenerationOptions::new().with_min_edit_distance(2);
+        for _ in 0..20 {
+            let sequence = mm.generate_with(&options).unwrap();
+            assert!(training_set
+                .iter()
+                .all(|trained| edit_distance(trained, &sequence) >= 2));
+        }
+    }
+
+    #[test]
+    fn novel_generation_requires_training_set() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .build();
+        let options = GenerationOptions::new().with_min_edit_distance(1);
+        assert_eq!(
+            mm.generate_with(&options),
+            Err(MultiMarkovError::NoTrainingSet)
+        );
+    }
+
+    #[test]
+    fn edit_distance_is_correct() {
+        let kitten: Vec<char> = "kitten".chars().collect();
+        let sitting: Vec<char> = "sitting".chars().collect();
+        assert_eq!(edit_distance(&kitten, &sitting), 3);
+        assert_eq!(edit_distance(&kitten, &kitten), 0);
+        assert_eq!(edit_distance(&kitten, &[]), 6);
+    }
+
+    #[test]
+    fn generated_sequences_start_with_prefix_and_end_with_suffix() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .with_rng(Box::new(SmallRng::seed_from_u64(1234)))
+            .train(char_data().into_iter())
+            .build();
+        let options = GenerationOptions::new()
+            .with_prefix(vec!['b', 'a'])
+            .with_suffix(vec!['o', 'o'])
+            .with_max_length(8);
+        for _ in 0..20 {
+            let sequence = mm.generate_with(&options).unwrap();
+            assert!(sequence.starts_with(&['b', 'a']));
+            assert!(sequence.ends_with(&['o', 'o']));
+            assert!(sequence.len() <= 8);
+        }
+    }
+
+    #[test]
+    fn suffix_is_reached_without_priors() {
+        // without priors, "ar" can only be reached through "ba" in "foobar"
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .with_rng(Box::new(SmallRng::seed_from_u64(1234)))
+            .train(char_data().into_iter())
+            .without_prior()
+            .build();
+        let options = GenerationOptions::new().with_suffix(vec!['a', 'r']);
+        for _ in 0..20 {
+            let sequence = mm.generate_with(&options).unwrap();
+            assert!(sequence.ends_with(&['b', 'a', 'r']));
+        }
+    }
+
+    #[test]
+    fn impossible_suffix_is_reported() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .without_prior()
+            .build();
+        // 'z' is never followed by anything but the end state
+        let options = GenerationOptions::new().with_suffix(vec!['z', 'a']);
+        assert_eq!(
+            mm.generate_with(&options),
+            Err(MultiMarkovError::Unsatisfiable)
+        );
+        let options = GenerationOptions::new()
+            .with_prefix(vec!['f', 'o', 'o'])
+            .with_suffix(vec!['a', 'r'])
+            .with_max_length(4);
+        assert_eq!(
+            mm.generate_with(&options),
+            Err(MultiMarkovError::Unsatisfiable)
+        );
+    }
+
+    #[test]
+    fn truncation_cuts_off_prior_only_transitions() {
+        let training_set: HashSet<Vec<char>> = char_data().into_iter().collect();
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .with_prior(0.1)
+            .with_rng(Box::new(SmallRng::seed_from_u64(1234)))
+            .train(char_data().into_iter())
+            .build();
+        // with generous priors, unobserved transitions are common, but they're never among the
+        // most likely states, so with top-k truncation only training data can be generated
+        let options = GenerationOptions::new().with_top_k(1);
+        for _ in 0..20 {
+            let sequence = mm.generate_with(&options).unwrap();
+            assert!(training_set.contains(&sequence));
+        }
+    }
+}
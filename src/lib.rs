@@ -1,11 +1,34 @@
+pub mod arpa;
+pub mod binary;
 pub mod builder;
+mod decoding;
+mod dot;
+pub mod error;
+pub mod evaluation;
+mod generation;
+mod sampling;
+mod scoring;
+#[cfg(feature = "serde")]
+mod serde_chain;
+pub mod training;
+pub mod tuning;
 
 use crate::builder::MultiMarkovBuilder;
-use rand::{Rng, RngCore};
+pub use crate::dot::DotOptions;
+pub use crate::error::{FormatError, MultiMarkovError};
+pub use crate::generation::GenerationOptions;
+pub use crate::sampling::SamplingOptions;
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore, SeedableRng};
+#[cfg(feature = "serde")]
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 use std::cmp::min;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{btree_map, btree_set, BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::hash::Hash;
+use std::iter::Peekable;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
 
 /// Multi-order Markov chain models with a Katz back-off, for procedural generation applications.
 ///
@@ -56,17 +79,48 @@ use std::hash::Hash;
 ///     .build();
 /// ```
 ///
-/// Use method `random_next` (see below) to use it to generate new sequences.
+/// Use method `random_next` (see below) to use it to generate new sequences, or, if the model was
+/// trained with start/end boundary states, method `generate` to produce whole sequences.
+///
+/// With the `serde` feature enabled, a trained model can be serialized and deserialized whenever
+/// `T` can.  The RNG is not serialized: a deserialized model gets a new, non-seeded RNG, which
+/// can be replaced by assigning to its `rng` field.  Deserializing fails if the order, prior, or
+/// sampling options are invalid.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MultiMarkov<T>
 where
     T: Eq + Hash + Clone + std::cmp::Ord,
 {
+    /// The weights of the state transitions observed in training: for each context, the states
+    /// that followed it, and how often.
+    #[cfg_attr(feature = "serde", serde(with = "serde_chain"))]
     pub markov_chain: HashMap<Vec<T>, BTreeMap<T, f64>>,
-    pub known_states: HashSet<T>,
+    /// The prior weight given to each unobserved transition, if any.  It isn't stored in the
+    /// Markov chain, but applied when drawing and scoring: from any context, any known state
+    /// (and the end boundary state, if there is one) that wasn't observed to follow it has this
+    /// weight.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_prior"))]
+    pub prior: Option<f64>,
+    pub known_states: BTreeSet<T>,
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_order"))]
     pub order: i32,
+    pub boundaries: Option<Boundaries<T>>,
+    pub training_set: Option<HashSet<Vec<T>>>,
+    pub sampling: SamplingOptions,
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_rng"))]
     pub rng: Box<dyn RngCore + Send + Sync>,
 }
 
+/// The states that mark the beginning and end of every training sequence, for example `'#'`
+/// before the first letter and after the last letter of a word.  The same value may be used for
+/// both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Boundaries<T> {
+    pub start: T,
+    pub end: T,
+}
+
 impl<T> MultiMarkov<T>
 where
     T: Eq + Hash + Clone + std::cmp::Ord,
@@ -80,40 +134,291 @@ where
     }
 
     /// Using the random-number generator and the "weights" of the various state transitions from
-    /// the trained model, draw a new state to follow the given sequence.
-    pub fn random_next(&mut self, current_sequence: &Vec<T>) -> Option<T> {
-        let r: f64 = self.rng.gen();
-        let bestmodel = self.best_model(current_sequence)?;
-        let sum_of_weights: f64 = bestmodel.values().sum();
-        let mut randomroll = r * sum_of_weights; // TODO: can this be accomplished in fewer lines?
-                                                 // every state has a chance of being selected in proportion to its 'weight' as fraction of the sum of weights
-        for (k, v) in bestmodel {
-            if randomroll > *v {
-                randomroll -= v;
-            } else {
-                return Some(k.clone());
-            }
-        }
-        None // this should never be reached
+    /// the trained model (reshaped by the model's `sampling` options), draw a new state to follow
+    /// the given sequence.
+    ///
+    /// The sequence can be any slice: a `Vec`, an array, or only its last `order` states, since
+    /// the model never looks further back than that.  A streaming generator can keep those in a
+    /// `VecDeque` ring buffer and pass `make_contiguous()`, without reallocating:
+    ///
+    /// ```
+    /// use multimarkov::MultiMarkov;
+    /// use std::collections::VecDeque;
+    /// let mut mm = MultiMarkov::<char>::builder()
+    ///     .with_order(2)
+    ///     .train(vec![vec!['f', 'o', 'o', 'b', 'a', 'r', 'f']].into_iter())
+    ///     .build();
+    /// let mut recent: VecDeque<char> = VecDeque::from(['f']);
+    /// for _ in 0..20 {
+    ///     let next = mm.random_next(recent.make_contiguous()).unwrap();
+    ///     if recent.len() == mm.order as usize {
+    ///         recent.pop_front();
+    ///     }
+    ///     recent.push_back(next);
+    /// }
+    /// assert_eq!(recent.len(), 2);
+    /// ```
+    pub fn random_next(&mut self, current_sequence: &[T]) -> Option<T> {
+        self.with_own_rng(|mm, rng| mm.random_next_with_rng(current_sequence, rng))
+    }
+
+    /// Like `random_next`, but draws from the given random-number generator instead of the
+    /// model's own, so it only needs a shared reference to the model.  This way one trained model
+    /// can be shared between threads (say in an `Arc`) and sampled concurrently without locks,
+    /// with each thread using its own RNG.
+    pub fn random_next_with_rng(&self, current_sequence: &[T], rng: &mut impl Rng) -> Option<T> {
+        let r: f64 = rng.random();
+        let (_, choices) = self.sampling_distribution(current_sequence, &self.sampling)?;
+        weighted_choice(choices.into_iter(), r).cloned()
+    }
+
+    /// Like `random_next`, but reports a `MultiMarkovError::NoContext` error if no trained model
+    /// matches any tail-end subsequence of the given sequence.
+    pub fn try_random_next(&mut self, current_sequence: &[T]) -> Result<T, MultiMarkovError> {
+        self.with_own_rng(|mm, rng| mm.try_random_next_with_rng(current_sequence, rng))
+    }
+
+    /// Like `try_random_next`, but draws from the given random-number generator.  See
+    /// `random_next_with_rng`.
+    pub fn try_random_next_with_rng(
+        &self,
+        current_sequence: &[T],
+        rng: &mut impl Rng,
+    ) -> Result<T, MultiMarkovError> {
+        let r: f64 = rng.random();
+        let (_, choices) = self
+            .sampling_distribution(current_sequence, &self.sampling)
+            .ok_or(MultiMarkovError::NoContext)?;
+        weighted_choice(choices.into_iter(), r)
+            .cloned()
+            .ok_or(MultiMarkovError::NoContext)
+    }
+
+    /// Lend the model's own RNG to a method that draws from a given RNG, and only needs a shared
+    /// reference to the model.  A placeholder takes the RNG's place in the meantime; the RNG is
+    /// put back even if `f` panics, so a model that survives the panic can still draw.
+    pub(crate) fn with_own_rng<R>(
+        &mut self,
+        f: impl FnOnce(&Self, &mut Box<dyn RngCore + Send + Sync>) -> R,
+    ) -> R {
+        let mut rng = mem::replace(&mut self.rng, Box::new(LentRng));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self, &mut rng)));
+        self.rng = rng;
+        result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+
+    /// Change the "prior probability" of the model's unobserved state transitions, without
+    /// retraining.  See `MultiMarkovBuilder::with_prior`.
+    pub fn set_prior(&mut self, prior: f64) {
+        self.prior = if prior == 0.0 { None } else { Some(prior) };
+    }
+
+    /// Like `set_prior`, but reports a `MultiMarkovError::InvalidPrior` error (and leaves the
+    /// prior unchanged) if the prior is negative, infinite, or NaN.
+    pub fn try_set_prior(&mut self, prior: f64) -> Result<(), MultiMarkovError> {
+        check_prior(prior)?;
+        self.set_prior(prior);
+        Ok(())
+    }
+
+    /// Remove the prior weights, so that the only state transitions possible are those seen in
+    /// the training data.
+    pub fn remove_prior(&mut self) {
+        self.set_prior(0.0);
+    }
+
+    /// The weight of the transition from a context in the Markov chain to a state: its observed
+    /// count, or the prior weight if it wasn't observed.  Returns `None` if there is no model for
+    /// exactly this context (see `random_next` for the back-off), or if the state can't follow it.
+    pub fn weight(&self, context: &[T], state: &T) -> Option<f64> {
+        self.transitions(self.markov_chain.get(context)?).get(state)
     }
 
     /// For a given sequence, find the most tightly-fitted model we have for its tail-end subsequence.
     /// For example, if the sequence is `['t','r','u','s']`, and self.order==3, first see if we have
     /// a model for `['r','u','s']`, which will only exist if that sequence has been seen in the training
     /// data.  If not, see if we have a model for `['u','s']`, and failing that, see if we have a
-    /// model for `['s']`.  If no model for `['s']` is found, return `None`.
-    fn best_model(&self, current_sequence: &Vec<T>) -> Option<&BTreeMap<T, f64>> {
+    /// model for `['s']`.  If no model for `['s']` is found, return `None`.  The model is returned
+    /// together with the subsequence (the "context") that it is for.
+    fn best_model(&self, current_sequence: &[T]) -> Option<(&Vec<T>, Transitions<'_, T>)> {
         // If current_sequence.len() is at least self.order, count "i" down from self.order to 1,
         // taking sequence slices of length "i" and checking if we have a matching model:
         for i in (1..(min(self.order as usize, current_sequence.len()) + 1)).rev() {
             let subsequence =
                 &current_sequence[(current_sequence.len() - i)..current_sequence.len()];
-            if self.markov_chain.contains_key(subsequence) {
-                return self.markov_chain.get(subsequence);
+            if let Some((context, observed)) = self.markov_chain.get_key_value(subsequence) {
+                return Some((context, self.transitions(observed)));
             }
         }
         None
     }
+
+    /// The observed and prior weights of the states that may follow a context in `markov_chain`.
+    fn transitions<'a>(&'a self, observed: &'a BTreeMap<T, f64>) -> Transitions<'a, T> {
+        Transitions {
+            observed,
+            prior: self.prior,
+            known_states: &self.known_states,
+            end: self.boundaries.as_ref().map(|b| &b.end),
+        }
+    }
+}
+
+/// The weights of the states that may follow a context: the counts observed in training, and
+/// the prior weight of every known state (and the end state) that was not observed.
+#[derive(Clone, Copy)]
+pub(crate) struct Transitions<'a, T> {
+    pub(crate) observed: &'a BTreeMap<T, f64>,
+    prior: Option<f64>,
+    known_states: &'a BTreeSet<T>,
+    end: Option<&'a T>,
+}
+
+impl<'a, T> Transitions<'a, T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// All the weights, observed and prior, in the order of the states.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&'a T, f64)> + Clone {
+        let (known_states, end) = match self.prior {
+            Some(_) => (Some(self.known_states.iter().peekable()), self.end),
+            None => (None, None),
+        };
+        MergedWeights {
+            observed: self.observed.iter().peekable(),
+            known_states,
+            end,
+            prior: self.prior.unwrap_or(0.0),
+        }
+    }
+
+    /// The weight of a state, or `None` if it can't follow the context.
+    pub(crate) fn get(&self, state: &T) -> Option<f64> {
+        if let Some(weight) = self.observed.get(state) {
+            return Some(*weight);
+        }
+        self.prior
+            .filter(|_| self.known_states.contains(state) || self.end == Some(state))
+    }
+
+    pub(crate) fn sum(&self) -> f64 {
+        self.iter().map(|(_, v)| v).sum()
+    }
+}
+
+/// Merges the observed weights of a context with the prior weight of every known state and the
+/// end state that were not observed, in the order of the states.
+#[derive(Clone)]
+struct MergedWeights<'a, T> {
+    observed: Peekable<btree_map::Iter<'a, T, f64>>,
+    known_states: Option<Peekable<btree_set::Iter<'a, T>>>,
+    end: Option<&'a T>,
+    prior: f64,
+}
+
+impl<'a, T> Iterator for MergedWeights<'a, T>
+where
+    T: Ord,
+{
+    type Item = (&'a T, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the first of the next observed state, the next known state, and the end state
+        let next_known = self.known_states.as_mut().and_then(|k| k.peek().copied());
+        let next_prior = match (next_known, self.end) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let next = match (self.observed.peek(), next_prior) {
+            (Some((a, _)), Some(b)) if *a <= b => self.observed.next().map(|(k, v)| (k, *v)),
+            (_, Some(b)) => Some((b, self.prior)),
+            (Some(_), None) => self.observed.next().map(|(k, v)| (k, *v)),
+            (None, None) => None,
+        }?;
+        // a state that was observed has no prior weight
+        if next_known == Some(next.0) {
+            self.known_states.as_mut()?.next();
+        }
+        if self.end == Some(next.0) {
+            self.end = None;
+        }
+        Some(next)
+    }
+}
+
+/// Check that a prior is a finite number, zero or greater.
+pub(crate) fn check_prior(prior: f64) -> Result<(), MultiMarkovError> {
+    if prior.is_finite() && prior >= 0.0 {
+        Ok(())
+    } else {
+        Err(MultiMarkovError::InvalidPrior { prior })
+    }
+}
+
+/// Deserializes a model's order, which must be positive, like the builder requires.
+#[cfg(feature = "serde")]
+fn deserialize_order<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    let order = i32::deserialize(deserializer)?;
+    if order > 0 {
+        Ok(order)
+    } else {
+        Err(D::Error::custom(MultiMarkovError::InvalidOrder { order }))
+    }
+}
+
+/// Deserializes a model's prior, which must pass `check_prior`.
+#[cfg(feature = "serde")]
+fn deserialize_prior<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    let prior = Option::<f64>::deserialize(deserializer)?;
+    prior
+        .map(check_prior)
+        .transpose()
+        .map_err(D::Error::custom)?;
+    Ok(prior)
+}
+
+/// Stands in for a model's RNG while `with_own_rng` lends it out.  It is never drawn from.
+struct LentRng;
+
+impl RngCore for LentRng {
+    fn next_u32(&mut self) -> u32 {
+        unreachable!("the model's RNG is lent out")
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        unreachable!("the model's RNG is lent out")
+    }
+
+    fn fill_bytes(&mut self, _dest: &mut [u8]) {
+        unreachable!("the model's RNG is lent out")
+    }
+}
+
+/// A new RNG, seeded from the thread-local RNG.
+pub(crate) fn default_rng() -> Box<dyn RngCore + Send + Sync> {
+    Box::new(SmallRng::seed_from_u64(rand::rng().random()))
+}
+
+/// Pick one of the given choices, using a random number `r` drawn uniformly from `[0, 1)`.  Every
+/// choice has a chance of being selected in proportion to its 'weight' as fraction of the sum of
+/// weights.  Returns `None` if there are no choices with a positive weight.
+fn weighted_choice<K>(weights: impl Iterator<Item = (K, f64)> + Clone, r: f64) -> Option<K> {
+    let sum_of_weights: f64 = weights.clone().map(|(_, v)| v).sum();
+    if sum_of_weights <= 0.0 {
+        return None;
+    }
+    let mut randomroll = r * sum_of_weights;
+    for (k, v) in weights {
+        if v <= 0.0 {
+            continue;
+        }
+        if randomroll > v {
+            randomroll -= v;
+        } else {
+            return Some(k);
+        }
+    }
+    None // this should never be reached
 }
 
 impl<T> fmt::Debug for MultiMarkov<T>
@@ -149,8 +454,8 @@ mod tests {
             .with_prior(0.015)
             .train(char_data().into_iter())
             .build();
-        assert!(mm.random_next(&vec!['a', 'b', 'c']).is_some()); // random draw didn't fail (because 'c' is in training data)
-        assert!(mm.random_next(&vec!['x', 'y', 'z']).is_none()); // 'z' is in training data only at end of sequence; no following states were observed so there's no model
+        assert!(mm.random_next(&['a', 'b', 'c']).is_some()); // random draw didn't fail (because 'c' is in training data)
+        assert!(mm.random_next(&['x', 'y', 'z']).is_none()); // 'z' is in training data only at end of sequence; no following states were observed so there's no model
     }
 
     #[test]
@@ -173,6 +478,162 @@ mod tests {
         let chain = &mm.markov_chain;
         assert_eq!(*chain.get(&*vec!['b']).unwrap().get(&'a').unwrap(), 2.0); // seen twice in training data
         assert_eq!(*chain.get(&*vec!['a']).unwrap().get(&'c').unwrap(), 1.0); // seen once in training data
-        assert_eq!(*chain.get(&*vec!['a']).unwrap().get(&'e').unwrap(), 0.001); // not observed in training data; assigned a 'prior' probability
+        assert!(!chain.get(&*vec!['a']).unwrap().contains_key(&'e')); // not observed in training data...
+        assert_eq!(mm.weight(&['a'], &'e'), Some(0.001)); // ...but assigned a 'prior' probability
+    }
+
+    #[test]
+    fn implicit_priors_fill_in_unobserved_transitions() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .with_prior(0.5)
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .build();
+        // only the observed transition is stored
+        assert_eq!(mm.markov_chain[&vec!['b']].len(), 1);
+        let (_, transitions) = mm.best_model(&['b']).unwrap();
+        let weights: Vec<(char, f64)> = transitions.iter().map(|(k, v)| (*k, v)).collect();
+        // every known state and the end state, in order
+        assert_eq!(
+            weights,
+            vec![
+                ('#', 0.5),
+                ('a', 2.0),
+                ('b', 0.5),
+                ('c', 0.5),
+                ('e', 0.5),
+                ('f', 0.5),
+                ('o', 0.5),
+                ('r', 0.5),
+                ('z', 0.5)
+            ]
+        );
+        assert_eq!(transitions.sum(), 6.0);
+        assert_eq!(transitions.get(&'a'), Some(2.0));
+        assert_eq!(transitions.get(&'x'), None);
+    }
+
+    #[test]
+    fn priors_can_be_changed_after_building() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .without_prior()
+            .train(char_data().into_iter())
+            .build();
+        // 'b' was only ever followed by 'a'
+        assert_eq!(mm.random_next(&['b']), Some('a'));
+        mm.set_prior(0.5);
+        assert_eq!(mm.prior, Some(0.5));
+        assert_eq!(mm.markov_chain[&vec!['b']].len(), 1);
+        assert_eq!(mm.weight(&['b'], &'c'), Some(0.5));
+        assert!((0..100).any(|_| mm.random_next(&['b']) != Some('a')));
+        mm.remove_prior();
+        assert_eq!(mm.prior, None);
+        assert_eq!(mm.weight(&['b'], &'c'), None);
+        assert_eq!(
+            mm.try_set_prior(-1.0),
+            Err(MultiMarkovError::InvalidPrior { prior: -1.0 })
+        );
+        assert_eq!(mm.prior, None);
+    }
+
+    #[test]
+    fn missing_contexts_are_reported() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .train(char_data().into_iter())
+            .build();
+        assert_eq!(mm.try_random_next(&['q']), Err(MultiMarkovError::NoContext));
+        assert_eq!(mm.random_next(&['q']), None);
+        assert!(mm.try_random_next(&['a']).is_ok());
+    }
+
+    #[test]
+    fn temperature_reshapes_random_draws() {
+        use rand::{rngs::SmallRng, SeedableRng};
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .with_prior(0.001)
+            .with_temperature(0.1)
+            .with_rng(Box::new(SmallRng::seed_from_u64(1234)))
+            .train(char_data().into_iter())
+            .build();
+        // 'b' -> 'a' was observed; at a low temperature the priors are practically never drawn
+        for _ in 0..100 {
+            assert_eq!(mm.random_next(&['b']), Some('a'));
+        }
+        // at a high temperature, the priors are drawn about as often as observed transitions
+        mm.sampling = SamplingOptions::new().with_temperature(100.0);
+        assert!((0..100).any(|_| mm.random_next(&['b']) != Some('a')));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_keeps_the_trained_model() {
+        use rand::{rngs::SmallRng, SeedableRng};
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .with_training_set()
+            .with_temperature(0.5)
+            .train(char_data().into_iter())
+            .build();
+        let json = serde_json::to_string(&mm).unwrap();
+        let mut loaded: MultiMarkov<char> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.markov_chain, mm.markov_chain);
+        assert_eq!(loaded.prior, mm.prior);
+        assert_eq!(loaded.known_states, mm.known_states);
+        assert_eq!(loaded.order, mm.order);
+        assert_eq!(loaded.boundaries, mm.boundaries);
+        assert_eq!(loaded.training_set, mm.training_set);
+        assert_eq!(loaded.sampling, mm.sampling);
+        // the RNG isn't saved, but can be replaced after loading
+        loaded.rng = Box::new(SmallRng::seed_from_u64(1234));
+        assert!(loaded.random_next(&['b', 'a']).is_some());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_invalid_settings() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .train(char_data().into_iter())
+            .build();
+        let json = serde_json::to_value(&mm).unwrap();
+        let load = |field: &str, value: serde_json::Value| {
+            let mut json = json.clone();
+            json[field] = value;
+            serde_json::from_value::<MultiMarkov<char>>(json).map(|_| ())
+        };
+        assert!(load("order", 2.into()).is_ok());
+        assert!(load("order", 0.into()).is_err());
+        assert!(load("prior", (-1.0).into()).is_err());
+        let sampling = |temperature: f64, top_k: usize, top_p: f64| serde_json::json!({ "temperature": temperature, "top_k": top_k, "top_p": top_p });
+        assert!(load("sampling", sampling(0.5, 3, 0.9)).is_ok());
+        assert!(load("sampling", sampling(0.0, 3, 0.9)).is_err());
+        assert!(load("sampling", sampling(0.5, 0, 0.9)).is_err());
+        assert!(load("sampling", sampling(0.5, 3, 1.5)).is_err());
+    }
+
+    #[test]
+    fn the_rng_survives_a_panic_while_it_is_lent_out() {
+        let seeded = || {
+            MultiMarkov::<char>::builder()
+                .with_order(2)
+                .with_prior(0.015)
+                .with_rng(Box::new(SmallRng::seed_from_u64(1234)))
+                .train(char_data().into_iter())
+                .build()
+        };
+        let mut mm = seeded();
+        let caught = panic::catch_unwind(AssertUnwindSafe(|| {
+            mm.with_own_rng(|_, _| -> Option<char> { panic!("interrupted") })
+        }));
+        assert!(caught.is_err());
+        assert_eq!(
+            mm.random_next(&['b', 'a']),
+            seeded().random_next(&['b', 'a'])
+        );
     }
 }
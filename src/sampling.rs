@@ -0,0 +1,274 @@
+use crate::MultiMarkov;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
+
+/// Weights of possible following states, in the order of the states.
+pub(crate) type Distribution<'a, T> = Vec<(&'a T, f64)>;
+
+/// Settings that reshape the distribution of possible following states before each random draw,
+/// to make generated sequences more conservative or more adventurous without retraining.
+///
+/// ```
+/// use multimarkov::SamplingOptions;
+/// let sampling = SamplingOptions::new()
+///     .with_temperature(0.5)
+///     .with_top_k(5)
+///     .with_top_p(0.9);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "UncheckedSamplingOptions")
+)]
+pub struct SamplingOptions {
+    temperature: f64,
+    top_k: Option<usize>,
+    top_p: Option<f64>,
+}
+
+impl SamplingOptions {
+    pub const DEFAULT_TEMPERATURE: f64 = 1.0;
+
+    /// Sampling options that leave the trained weights as they are.
+    pub fn new() -> Self {
+        Self {
+            temperature: Self::DEFAULT_TEMPERATURE,
+            top_k: None,
+            top_p: None,
+        }
+    }
+
+    /// Specifies the "temperature" of random draws.  Each weight (including prior weights) is
+    /// raised to the power of `1 / temperature` before drawing.  Values below 1.0 sharpen the
+    /// distribution, so that likely transitions become even more likely and output resembles the
+    /// training data; values above 1.0 flatten it, so that output becomes wilder.  Must be a
+    /// positive number.
+    ///
+    /// The default is SamplingOptions::DEFAULT_TEMPERATURE
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        if let Err(message) = check_temperature(temperature) {
+            panic!("{}", message);
+        }
+        self.temperature = temperature;
+        self
+    }
+
+    /// Limits each random draw to the `top_k` most likely states, cutting off the long tail of
+    /// unlikely (for example, prior-only) transitions.  Must be greater than zero.
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        if let Err(message) = check_top_k(top_k) {
+            panic!("{}", message);
+        }
+        self.top_k = Some(top_k);
+        self
+    }
+
+    /// Limits each random draw to the smallest set of most likely states whose probabilities add
+    /// up to at least `top_p` ("nucleus" sampling).  Must be greater than zero and at most 1.0.
+    pub fn with_top_p(mut self, top_p: f64) -> Self {
+        if let Err(message) = check_top_p(top_p) {
+            panic!("{}", message);
+        }
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    pub fn top_k(&self) -> Option<usize> {
+        self.top_k
+    }
+
+    pub fn top_p(&self) -> Option<f64> {
+        self.top_p
+    }
+
+    /// Reshape a distribution of weights according to these options: first the temperature is
+    /// applied, then the distribution is truncated to the top-k and top-p states.
+    pub(crate) fn apply<'a, T>(
+        &self,
+        weights: impl Iterator<Item = (&'a T, f64)>,
+    ) -> Distribution<'a, T> {
+        let weights: Distribution<T> = weights.collect();
+        let weights = self.apply_temperature(weights);
+        self.truncate(weights)
+    }
+
+    fn apply_temperature<'a, T>(&self, weights: Distribution<'a, T>) -> Distribution<'a, T> {
+        if self.temperature == 1.0 {
+            return weights;
+        }
+        // scale by the largest weight first, so that small temperatures can't overflow
+        let max_weight = weights.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+        if max_weight <= 0.0 {
+            return weights;
+        }
+        weights
+            .into_iter()
+            .map(|(k, v)| (k, (v / max_weight).powf(1.0 / self.temperature)))
+            .collect()
+    }
+
+    /// Drops the states that don't make the top-k or top-p cut, keeping the rest in their
+    /// original order.  Among states with equal weights, the earlier ones are kept.
+    fn truncate<'a, T>(&self, weights: Distribution<'a, T>) -> Distribution<'a, T> {
+        if self.top_k.is_none() && self.top_p.is_none() {
+            return weights;
+        }
+        let mut ranking: Vec<usize> = (0..weights.len()).collect();
+        ranking.sort_by(|a, b| weights[*b].1.total_cmp(&weights[*a].1));
+
+        let mut keep_count = self.top_k.unwrap_or(weights.len()).min(weights.len());
+        if let Some(top_p) = self.top_p {
+            let sum_of_weights: f64 = weights.iter().map(|(_, v)| v).sum();
+            let mut cumulative_weight = 0.0;
+            for (count, index) in ranking.iter().enumerate().take(keep_count) {
+                cumulative_weight += weights[*index].1;
+                if cumulative_weight >= top_p * sum_of_weights {
+                    keep_count = count + 1;
+                    break;
+                }
+            }
+        }
+
+        let mut keep = vec![false; weights.len()];
+        for index in &ranking[..keep_count] {
+            keep[*index] = true;
+        }
+        weights
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(weight, keep)| if keep { Some(weight) } else { None })
+            .collect()
+    }
+}
+
+impl Default for SamplingOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn check_temperature(temperature: f64) -> Result<(), &'static str> {
+    if temperature > 0.0 && temperature.is_finite() {
+        Ok(())
+    } else {
+        Err("Temperature must be a positive number.")
+    }
+}
+
+fn check_top_k(top_k: usize) -> Result<(), &'static str> {
+    if top_k > 0 {
+        Ok(())
+    } else {
+        Err("Top-k must be an integer greater than zero.")
+    }
+}
+
+fn check_top_p(top_p: f64) -> Result<(), &'static str> {
+    if top_p > 0.0 && top_p <= 1.0 {
+        Ok(())
+    } else {
+        Err("Top-p must be greater than zero and at most 1.0.")
+    }
+}
+
+/// Sampling options as they were saved, before they are checked like the `with_*` methods
+/// check them.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct UncheckedSamplingOptions {
+    temperature: f64,
+    top_k: Option<usize>,
+    top_p: Option<f64>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedSamplingOptions> for SamplingOptions {
+    type Error = &'static str;
+
+    fn try_from(options: UncheckedSamplingOptions) -> Result<Self, Self::Error> {
+        check_temperature(options.temperature)?;
+        options.top_k.map(check_top_k).transpose()?;
+        options.top_p.map(check_top_p).transpose()?;
+        Ok(Self {
+            temperature: options.temperature,
+            top_k: options.top_k,
+            top_p: options.top_p,
+        })
+    }
+}
+
+impl<T> MultiMarkov<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// The weights of the states that may follow the given sequence, from the best model for it,
+    /// reshaped by the given sampling options.  Also returns the context of the best model.
+    pub(crate) fn sampling_distribution(
+        &self,
+        current_sequence: &[T],
+        sampling: &SamplingOptions,
+    ) -> Option<(&Vec<T>, Distribution<'_, T>)> {
+        let (context, transitions) = self.best_model(current_sequence)?;
+        Some((context, sampling.apply(transitions.iter())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperature_sharpens_and_flattens() {
+        let (a, b) = ('a', 'b');
+        let weights = vec![(&a, 4.0), (&b, 1.0)];
+        let cold = SamplingOptions::new().with_temperature(0.5);
+        assert_eq!(
+            cold.apply(weights.clone().into_iter()),
+            vec![(&a, 1.0), (&b, 0.0625)]
+        );
+        let hot = SamplingOptions::new().with_temperature(2.0);
+        assert_eq!(
+            hot.apply(weights.clone().into_iter()),
+            vec![(&a, 1.0), (&b, 0.5)]
+        );
+        let neutral = SamplingOptions::new();
+        assert_eq!(neutral.apply(weights.clone().into_iter()), weights);
+    }
+
+    #[test]
+    fn truncation_keeps_the_most_likely_states() {
+        let (a, b, c, d) = ('a', 'b', 'c', 'd');
+        let weights = vec![(&a, 1.0), (&b, 5.0), (&c, 3.0), (&d, 1.0)];
+        let top_k = SamplingOptions::new().with_top_k(2);
+        assert_eq!(
+            top_k.apply(weights.clone().into_iter()),
+            vec![(&b, 5.0), (&c, 3.0)]
+        );
+        // 'b' alone is half of the total weight; 'b' and 'c' are 80%
+        let top_p = SamplingOptions::new().with_top_p(0.5);
+        assert_eq!(top_p.apply(weights.clone().into_iter()), vec![(&b, 5.0)]);
+        let top_p = SamplingOptions::new().with_top_p(0.6);
+        assert_eq!(
+            top_p.apply(weights.clone().into_iter()),
+            vec![(&b, 5.0), (&c, 3.0)]
+        );
+        // among equal weights, the earlier state is kept
+        let both = SamplingOptions::new().with_top_k(3).with_top_p(1.0);
+        assert_eq!(
+            both.apply(weights.clone().into_iter()),
+            vec![(&a, 1.0), (&b, 5.0), (&c, 3.0)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Temperature must be a positive number.")]
+    fn temperature_must_be_positive() {
+        let _sampling = SamplingOptions::new().with_temperature(0.0);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::MultiMarkov;
+use std::hash::Hash;
+use std::iter::once;
+
+impl<T> MultiMarkov<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// The probability that `random_next` would draw `state` to follow the given sequence, based
+    /// on the weights (including any prior weights) of the same model that `random_next` would
+    /// back off to.  The model's `sampling` options are not applied.  Returns `None` if there is
+    /// no model for the sequence, and `Some(0.0)` if there is one but `state` can't follow it.
+    pub fn probability_of_next(&self, current_sequence: &[T], state: &T) -> Option<f64> {
+        let (_, transitions) = self.best_model(current_sequence)?;
+        let sum_of_weights = transitions.sum();
+        Some(match transitions.get(state) {
+            Some(weight) => weight / sum_of_weights,
+            None => 0.0,
+        })
+    }
+
+    /// The log-likelihood (natural logarithm of the probability) of the model generating the given
+    /// sequence: the sum of the log-probabilities of each of its state transitions.  If the model
+    /// has boundaries, the sequence is wrapped in them, so the likelihood also covers how the
+    /// sequence begins and ends; otherwise, the first state is taken as given.
+    ///
+    /// Returns `None` if there is no model for some part of the sequence (for example, because
+    /// it contains a state that was never seen in training data), or if the sequence has no
+    /// transitions to score (a single state, in a model without boundaries).  Returns negative
+    /// infinity if some transition is impossible (for example, an unobserved transition in a
+    /// model without priors).
+    pub fn log_likelihood(&self, sequence: &[T]) -> Option<f64> {
+        if self.boundaries.is_none() && sequence.len() < 2 {
+            return None;
+        }
+        let sequence: Vec<T> = match &self.boundaries {
+            Some(boundaries) => once(boundaries.start.clone())
+                .chain(sequence.iter().cloned())
+                .chain(once(boundaries.end.clone()))
+                .collect(),
+            None => sequence.to_vec(),
+        };
+        let mut log_likelihood = 0.0;
+        for i in 1..sequence.len() {
+            log_likelihood += self.probability_of_next(&sequence[..i], &sequence[i])?.ln();
+        }
+        Some(log_likelihood)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn char_data() -> Vec<Vec<char>> {
+        vec![
+            vec!['a', 'c', 'e'],
+            vec!['f', 'o', 'o', 'b', 'a', 'r'],
+            vec!['b', 'a', 'z'],
+        ]
+    }
+
+    #[test]
+    fn probability_of_next_includes_priors() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .with_prior(0.5)
+            .train(char_data().into_iter())
+            .build();
+        // after 'b', 'a' has a weight of 2.0, and the other 6 known states have priors of 0.5
+        // ('f' is not a known state, because it was only seen at the start of a sequence)
+        assert_eq!(mm.probability_of_next(&['b'], &'a'), Some(2.0 / 5.0));
+        assert_eq!(mm.probability_of_next(&['b'], &'c'), Some(0.5 / 5.0));
+        assert_eq!(mm.probability_of_next(&['b'], &'f'), Some(0.0));
+        assert_eq!(mm.probability_of_next(&['x'], &'a'), None);
+    }
+
+    #[test]
+    fn log_likelihood_covers_boundaries() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .without_prior()
+            .build();
+        // '#' -> 'a' has probability 1/3, and the rest of "ace" follows with certainty
+        let log_likelihood = mm.log_likelihood(&['a', 'c', 'e']).unwrap();
+        assert!((log_likelihood - (1.0f64 / 3.0).ln()).abs() < 1e-12);
+        // "ac" is never followed by the end state
+        assert_eq!(mm.log_likelihood(&['a', 'c']), Some(f64::NEG_INFINITY));
+        // 'x' was never seen, so there's no model for what follows it
+        assert_eq!(mm.log_likelihood(&['x', 'a']), None);
+    }
+}
@@ -0,0 +1,27 @@
+//! Serializes the Markov chain as a sequence of (context, transitions) pairs, sorted by context,
+//! because formats such as JSON only allow strings as map keys.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+type Chain<T> = HashMap<Vec<T>, BTreeMap<T, f64>>;
+
+pub fn serialize<T, S>(markov_chain: &Chain<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + Ord,
+    S: Serializer,
+{
+    let mut pairs: Vec<_> = markov_chain.iter().collect();
+    pairs.sort_by(|a, b| a.0.cmp(b.0));
+    serializer.collect_seq(pairs)
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Chain<T>, D::Error>
+where
+    T: Deserialize<'de> + Eq + Hash + Ord,
+    D: Deserializer<'de>,
+{
+    let pairs: Vec<(Vec<T>, BTreeMap<T, f64>)> = Vec::deserialize(deserializer)?;
+    Ok(pairs.into_iter().collect())
+}
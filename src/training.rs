@@ -0,0 +1,913 @@
+use crate::{Boundaries, MultiMarkov, MultiMarkovError};
+use log::debug;
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+
+/// Stored weights within this fraction of the weight being untrained count as equal to it, since
+/// sums of fractional weights pick up rounding errors (ten weights of 0.1 don't add up to
+/// exactly 1.0).
+const WEIGHT_TOLERANCE: f64 = 1e-9;
+
+/// Why a training sequence was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RejectionReason {
+    /// The sequence was empty.
+    Empty,
+    /// The sequence had fewer than two states, in a model without boundaries, so no transition
+    /// could be observed in it.
+    TooShort,
+    /// The sequence's weight was zero or less, or not a finite number.
+    InvalidWeight,
+    /// The sequence was to be untrained, but the model doesn't contain all of its observations.
+    NotTrained,
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectionReason::Empty => write!(f, "sequence was empty"),
+            RejectionReason::TooShort => {
+                write!(
+                    f,
+                    "sequence was too short, must contain at least two states"
+                )
+            }
+            RejectionReason::InvalidWeight => {
+                write!(f, "weight must be a positive, finite number")
+            }
+            RejectionReason::NotTrained => write!(f, "sequence was never trained"),
+        }
+    }
+}
+
+/// What happened when training a batch of sequences: which ones were trained, which ones were
+/// rejected and why, and some statistics on the trained sequences.  Returned by
+/// `MultiMarkov::train`; a builder accumulates one for all of its training (see
+/// `MultiMarkovBuilder::training_report`).
+///
+/// `MultiMarkov::untrain` returns the same report for the sequences it removed: there, sequences
+/// that were never trained are rejected as `RejectionReason::NotTrained`, and `new_states` is
+/// always zero.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TrainingReport {
+    /// The number of sequences that were trained.
+    pub accepted: usize,
+    /// The index of each rejected sequence, counting from zero in the order the sequences were
+    /// given, and the reason it was rejected.  A builder's report counts across all of its
+    /// training calls; the report of one `MultiMarkov::train` call counts within that call.
+    /// `RejectedSequence` errors from strict training count the same way.
+    pub rejected: Vec<(usize, RejectionReason)>,
+    /// The length of the shortest trained sequence, not counting boundary states.
+    pub shortest: Option<usize>,
+    /// The length of the longest trained sequence, not counting boundary states.
+    pub longest: Option<usize>,
+    /// The total number of states in the trained sequences, not counting boundary states.
+    pub total_length: usize,
+    /// The number of states that the trained sequences added to the model's known states.
+    pub new_states: usize,
+    /// The number of known states in the model after training.
+    pub alphabet_size: usize,
+}
+
+impl TrainingReport {
+    /// The number of sequences given for training, whether trained or rejected.
+    pub fn sequences(&self) -> usize {
+        self.accepted + self.rejected.len()
+    }
+
+    /// The average length of a trained sequence.  Returns `None` if nothing was trained.
+    pub fn mean_length(&self) -> Option<f64> {
+        if self.accepted == 0 {
+            return None;
+        }
+        Some(self.total_length as f64 / self.accepted as f64)
+    }
+
+    /// Count one more sequence as trained, or as rejected for the given reason.
+    fn record(&mut self, index: usize, length: usize, result: Result<(), RejectionReason>) {
+        match result {
+            Ok(()) => {
+                self.accepted += 1;
+                self.shortest = Some(min(self.shortest.unwrap_or(usize::MAX), length));
+                self.longest = Some(max(self.longest.unwrap_or(0), length));
+                self.total_length += length;
+            }
+            Err(reason) => {
+                debug!("Rejected sequence {}: {}", index, reason);
+                self.rejected.push((index, reason));
+            }
+        }
+    }
+
+    /// Add a later report to this one, as if all the sequences had been given at once.
+    pub(crate) fn extend(&mut self, later: TrainingReport) {
+        let offset = self.sequences();
+        self.accepted += later.accepted;
+        self.rejected.extend(
+            later
+                .rejected
+                .into_iter()
+                .map(|(index, reason)| (index + offset, reason)),
+        );
+        self.shortest = self.shortest.into_iter().chain(later.shortest).min();
+        self.longest = self.longest.into_iter().chain(later.longest).max();
+        self.total_length += later.total_length;
+        self.new_states += later.new_states;
+        self.alphabet_size = later.alphabet_size;
+    }
+}
+
+impl fmt::Display for TrainingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} sequences trained",
+            self.accepted,
+            self.sequences()
+        )?;
+        if let (Some(shortest), Some(longest), Some(mean)) =
+            (self.shortest, self.longest, self.mean_length())
+        {
+            write!(
+                f,
+                " (lengths {} to {}, mean {:.2})",
+                shortest, longest, mean
+            )?;
+        }
+        write!(
+            f,
+            "; {} new states, {} known states",
+            self.new_states, self.alphabet_size
+        )
+    }
+}
+
+/// The parts of a model that training changes, borrowed from a `MultiMarkovBuilder` or from a
+/// built `MultiMarkov`, so that both train sequences the same way.
+pub(crate) struct Trainer<'a, T> {
+    pub(crate) markov_chain: &'a mut HashMap<Vec<T>, BTreeMap<T, f64>>,
+    pub(crate) known_states: &'a mut BTreeSet<T>,
+    pub(crate) training_set: &'a mut Option<HashSet<Vec<T>>>,
+    pub(crate) order: i32,
+    pub(crate) boundaries: Option<&'a Boundaries<T>>,
+}
+
+impl<'a, T> Trainer<'a, T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// Train each of the sequences with its weight, skipping the ones that can't be trained, and
+    /// report what happened.
+    pub(crate) fn train(
+        &mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+    ) -> TrainingReport {
+        let known_before = self.known_states.len();
+        let mut report = TrainingReport::default();
+        for (index, (sequence, weight)) in sequences.enumerate() {
+            let sequence = sequence.as_ref();
+            let result = self.train_sequence(sequence, weight);
+            report.record(index, sequence.len(), result);
+        }
+        report.alphabet_size = self.known_states.len();
+        report.new_states = report.alphabet_size - known_before;
+        debug!(
+            "{} sequences successfully trained; {} errors.",
+            report.accepted,
+            report.rejected.len()
+        );
+        report
+    }
+
+    /// Train the sequences like `train`, but only if none of them would be rejected.  Otherwise
+    /// the model is left unchanged, and the first rejected sequence is reported as an error,
+    /// counting the sequences from `first_index`.
+    pub(crate) fn try_train(
+        &mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+        first_index: usize,
+    ) -> Result<TrainingReport, MultiMarkovError> {
+        let sequences: Vec<_> = sequences.collect();
+        for (index, (sequence, weight)) in sequences.iter().enumerate() {
+            self.check(sequence.as_ref(), *weight).map_err(|reason| {
+                MultiMarkovError::RejectedSequence {
+                    index: first_index + index,
+                    reason,
+                }
+            })?;
+        }
+        Ok(self.train(sequences.into_iter()))
+    }
+
+    /// Learn all the transitions possible from one training sequence, adding observations to the
+    /// Markov model.  Each observation counts as `weight` (1.0 for ordinary training).  States and
+    /// contexts are only cloned into the model the first time they are seen.  The one exception
+    /// is `prefix`: with boundaries, up to `order` states are cloned for every sequence.
+    pub(crate) fn train_sequence(
+        &mut self,
+        sequence: &[T],
+        weight: f64,
+    ) -> Result<(), RejectionReason> {
+        self.check(sequence, weight)?;
+
+        // Build a running set of all known states while we're at it
+        for state in self.alphabet(sequence) {
+            if !self.known_states.contains(state) {
+                self.known_states.insert(state.clone());
+            }
+        }
+
+        // For each context preceding a state, record that the state was observed following it.
+        // IE if the sequence is ['R','U','S','T'] and this is a 3rd-order model, then for the three models ['S'], ['U','S'], and ['R','U','S'] we record that ['T'] is a known follower.
+        let prefix = self.prefix(sequence);
+        for (context, state) in observed_transitions(sequence, &prefix, self.end(), self.order) {
+            if let Some(transitions_from) = self.markov_chain.get_mut(context) {
+                // "from" sequence has been seen before
+                if let Some(observed) = transitions_from.get_mut(state) {
+                    // it has been seen before with this transition; add one (weighted) observance
+                    *observed += weight;
+                } else {
+                    // it hasn't been seen before with this transition; insert transition with one observance
+                    transitions_from.insert(state.clone(), weight);
+                }
+            } else {
+                // "from" sequence hasn't been seen before; add it and add the observed transition
+                let mut observed_transition = BTreeMap::new();
+                observed_transition.insert(state.clone(), weight);
+                self.markov_chain
+                    .insert(context.to_vec(), observed_transition);
+            }
+        }
+
+        if let Some(training_set) = self.training_set {
+            if !training_set.contains(sequence) {
+                training_set.insert(sequence.to_vec());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove each of the sequences' contributions from the model, skipping the ones that can't
+    /// be untrained, and report what happened.
+    pub(crate) fn untrain(
+        &mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+    ) -> TrainingReport {
+        let mut report = TrainingReport::default();
+        let mut followers = self.count_followers();
+        for (index, (sequence, weight)) in sequences.enumerate() {
+            let sequence = sequence.as_ref();
+            let result = self.untrain_sequence(sequence, weight, &mut followers);
+            report.record(index, sequence.len(), result);
+        }
+        report.alphabet_size = self.known_states.len();
+        debug!(
+            "{} sequences successfully untrained; {} errors.",
+            report.accepted,
+            report.rejected.len()
+        );
+        report
+    }
+
+    /// Untrain the sequences like `untrain`, but only if none of them would be rejected.
+    /// Otherwise the model is left unchanged, and the first rejected sequence is reported as an
+    /// error, counting the sequences from `first_index`.
+    pub(crate) fn try_untrain(
+        &mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+        first_index: usize,
+    ) -> Result<TrainingReport, MultiMarkovError> {
+        let sequences: Vec<_> = sequences.collect();
+        let prefixes: Vec<Vec<T>> = sequences
+            .iter()
+            .map(|(sequence, _)| self.prefix(sequence.as_ref()))
+            .collect();
+        // the whole batch together must not take away more observations than the model has
+        let mut observations: HashMap<(&[T], &T), f64> = HashMap::new();
+        for (index, ((sequence, weight), prefix)) in sequences.iter().zip(&prefixes).enumerate() {
+            let rejected = |reason| MultiMarkovError::RejectedSequence {
+                index: first_index + index,
+                reason,
+            };
+            self.check(sequence.as_ref(), *weight).map_err(rejected)?;
+            for transition in
+                observed_transitions(sequence.as_ref(), prefix, self.end(), self.order)
+            {
+                let count = observations.entry(transition).or_insert(0.0);
+                *count += weight;
+                if !self.has_observed(transition, *count) {
+                    return Err(rejected(RejectionReason::NotTrained));
+                }
+            }
+        }
+        Ok(self.untrain(sequences.into_iter()))
+    }
+
+    /// Subtract exactly the observations that `train_sequence` added for one sequence with the
+    /// same weight.  Nothing is changed if the model doesn't contain all of them, i.e. if the
+    /// sequence was never trained (with at least that weight).  `followers` comes from
+    /// `count_followers`, and is kept up to date.
+    pub(crate) fn untrain_sequence(
+        &mut self,
+        sequence: &[T],
+        weight: f64,
+        followers: &mut Option<HashMap<T, usize>>,
+    ) -> Result<(), RejectionReason> {
+        self.check(sequence, weight)?;
+
+        // count how many times the sequence observed each transition, and make sure the model has
+        // at least that many before changing anything
+        let prefix = self.prefix(sequence);
+        let mut observations: HashMap<(&[T], &T), f64> = HashMap::new();
+        for transition in observed_transitions(sequence, &prefix, self.end(), self.order) {
+            *observations.entry(transition).or_insert(0.0) += weight;
+        }
+        if !observations
+            .iter()
+            .all(|(transition, count)| self.has_observed(*transition, *count))
+        {
+            return Err(RejectionReason::NotTrained);
+        }
+
+        for ((context, state), count) in observations {
+            let transitions_from = self.markov_chain.get_mut(context).unwrap();
+            let stored = transitions_from.get_mut(state).unwrap();
+            *stored -= count;
+            if *stored <= count * WEIGHT_TOLERANCE {
+                transitions_from.remove(state);
+                let context_removed = transitions_from.is_empty();
+                if context_removed {
+                    self.markov_chain.remove(context);
+                }
+                // forget states that no longer appear in the training data
+                if context.len() == 1 {
+                    match followers {
+                        Some(followers) => {
+                            let count = followers.get_mut(state).unwrap();
+                            *count -= 1;
+                            if *count == 0 {
+                                self.known_states.remove(state);
+                            }
+                        }
+                        None if context_removed => {
+                            self.known_states.remove(&context[0]);
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+
+        if let Some(training_set) = self.training_set {
+            training_set.remove(sequence);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the model holds at least `count` observations of the transition, give or take
+    /// rounding errors.
+    fn has_observed(&self, (context, state): (&[T], &T), count: f64) -> bool {
+        self.markov_chain
+            .get(context)
+            .and_then(|transitions| transitions.get(state))
+            .is_some_and(|stored| *stored >= count * (1.0 - WEIGHT_TOLERANCE))
+    }
+
+    /// For a model without boundaries, how many single-state contexts each state follows.  A
+    /// state is known as long as it follows at least one.  Models with boundaries don't need
+    /// this: every state of their training sequences is followed by another state or the end
+    /// state, so a state is known as long as there is a single-state context of it.
+    fn count_followers(&self) -> Option<HashMap<T, usize>> {
+        if self.boundaries.is_some() {
+            return None;
+        }
+        let mut followers = HashMap::new();
+        for (_, transitions) in self.markov_chain.iter().filter(|(c, _)| c.len() == 1) {
+            for state in transitions.keys() {
+                *followers.entry(state.clone()).or_insert(0) += 1;
+            }
+        }
+        Some(followers)
+    }
+
+    /// Check that a sequence can be trained: that its weight is positive, and that it's long
+    /// enough to observe any transitions, counting the boundary states if the model has them.
+    fn check(&self, sequence: &[T], weight: f64) -> Result<(), RejectionReason> {
+        // also rejects NaN
+        if !(weight > 0.0 && weight.is_finite()) {
+            return Err(RejectionReason::InvalidWeight);
+        }
+        match self.boundaries {
+            _ if sequence.is_empty() => Err(RejectionReason::Empty),
+            None if sequence.len() < 2 => Err(RejectionReason::TooShort),
+            _ => Ok(()),
+        }
+    }
+
+    /// The states of a sequence that are observed following some context, which make up the
+    /// model's alphabet.  That's all of them if the model has boundaries (the end boundary state
+    /// isn't part of the training data's alphabet), otherwise all but the first.
+    fn alphabet<'s>(&self, sequence: &'s [T]) -> &'s [T] {
+        match self.boundaries {
+            Some(_) => sequence,
+            None => &sequence[1..],
+        }
+    }
+
+    /// The start boundary state followed by as many of the sequence's first states as can be in a
+    /// context with it, if the model has boundaries.  The contexts that begin at the start state
+    /// aren't slices of the sequence, so they can only be looked up in the chain from a copy.
+    fn prefix(&self, sequence: &[T]) -> Vec<T> {
+        match self.boundaries {
+            Some(boundaries) => {
+                let length = min(sequence.len(), (self.order as usize).saturating_sub(1));
+                let mut prefix = Vec::with_capacity(length + 1);
+                prefix.push(boundaries.start.clone());
+                prefix.extend_from_slice(&sequence[..length]);
+                prefix
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn end(&self) -> Option<&'a T> {
+        self.boundaries.map(|boundaries| &boundaries.end)
+    }
+}
+
+/// Every transition observed in a sequence, as pairs of a context of up to `order` states and the
+/// state that followed it.  If the model has boundaries, `prefix` holds the start boundary state
+/// and the first states of the sequence, for the contexts that begin at the start boundary, and
+/// `end` is the end boundary state, which follows the last state.  This way the sequence doesn't
+/// need to be copied to put the boundary states around it.
+fn observed_transitions<'s, T>(
+    sequence: &'s [T],
+    prefix: &'s [T],
+    end: Option<&'s T>,
+    order: i32,
+) -> impl Iterator<Item = (&'s [T], &'s T)> {
+    let bounded = end.is_some();
+    // the length of the sequence with its boundary states
+    let length = if bounded {
+        sequence.len() + 2
+    } else {
+        sequence.len()
+    };
+    (1..length).flat_map(move |i| {
+        let state = match end {
+            Some(end) if i == length - 1 => end,
+            Some(_) => &sequence[i - 1],
+            None => &sequence[i],
+        };
+        ((max(0, i as i32 - order) as usize)..i).map(move |j| {
+            let context = if !bounded {
+                &sequence[j..i]
+            } else if j == 0 {
+                &prefix[..i]
+            } else {
+                &sequence[j - 1..i - 1]
+            };
+            (context, state)
+        })
+    })
+}
+
+impl<T> MultiMarkov<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// Ingest more training sequences into a model that has already been built, for example to
+    /// keep learning from new data over time.  They are trained exactly as if they had been given
+    /// to the builder.  Since priors are applied implicitly, any newly discovered states get the
+    /// model's prior weight right away.  Sequences that can't be trained are skipped; the
+    /// returned report lists them.
+    pub fn train(&mut self, sequences: impl Iterator<Item = impl AsRef<[T]>>) -> TrainingReport {
+        self.trainer()
+            .train(sequences.map(|sequence| (sequence, 1.0)))
+    }
+
+    /// Ingest more weighted training sequences into a model that has already been built.  See
+    /// `MultiMarkovBuilder::train_weighted`.
+    pub fn train_weighted(
+        &mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+    ) -> TrainingReport {
+        self.trainer().train(sequences)
+    }
+
+    /// Strict version of `train`: if any sequence would be rejected, returns a
+    /// `MultiMarkovError::RejectedSequence` error for the first one, and leaves the model
+    /// unchanged.
+    pub fn try_train(
+        &mut self,
+        sequences: impl Iterator<Item = impl AsRef<[T]>>,
+    ) -> Result<TrainingReport, MultiMarkovError> {
+        self.trainer()
+            .try_train(sequences.map(|sequence| (sequence, 1.0)), 0)
+    }
+
+    /// Strict version of `train_weighted`.  See `try_train`.
+    pub fn try_train_weighted(
+        &mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+    ) -> Result<TrainingReport, MultiMarkovError> {
+        self.trainer().try_train(sequences, 0)
+    }
+
+    /// Remove sequences from a model that has already been built, for example to take out an
+    /// offensive name that slipped into the training data.  Subtracts exactly the observations
+    /// that training each sequence added; transitions and contexts that fall to zero are removed,
+    /// and so are states that no longer appear in any transition.  Sequences that were never
+    /// trained are skipped, and reported as rejected with `RejectionReason::NotTrained`.  A
+    /// sequence that was trained several times must be untrained as many times to remove it
+    /// entirely, but it is removed from the training set (if any) at once.  Untraining subtracts
+    /// one observation per transition; use `untrain_weighted` for sequences that were trained
+    /// with `train_weighted`.
+    ///
+    /// ```
+    /// use multimarkov::training::RejectionReason;
+    /// use multimarkov::MultiMarkov;
+    /// let mut mm = MultiMarkov::<char>::builder()
+    ///     .train(vec![vec!['a', 'b'], vec!['c', 'd']].into_iter())
+    ///     .build();
+    /// let report = mm.untrain(vec![vec!['a', 'b'], vec!['x', 'y']].into_iter());
+    /// assert_eq!(report.accepted, 1);
+    /// assert_eq!(report.rejected, vec![(1, RejectionReason::NotTrained)]);
+    /// ```
+    pub fn untrain(&mut self, sequences: impl Iterator<Item = impl AsRef<[T]>>) -> TrainingReport {
+        self.trainer()
+            .untrain(sequences.map(|sequence| (sequence, 1.0)))
+    }
+
+    /// Remove `(sequence, weight)` pairs from a model that has already been built, subtracting
+    /// `weight` observations per transition, so that untraining a sequence with the weight it was
+    /// trained with (see `MultiMarkovBuilder::train_weighted`) removes it exactly.  See `untrain`.
+    pub fn untrain_weighted(
+        &mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+    ) -> TrainingReport {
+        self.trainer().untrain(sequences)
+    }
+
+    /// Strict version of `untrain`: if any sequence would be rejected, for example because it
+    /// was never trained, returns a `MultiMarkovError::RejectedSequence` error for the first one,
+    /// and leaves the model unchanged.
+    pub fn try_untrain(
+        &mut self,
+        sequences: impl Iterator<Item = impl AsRef<[T]>>,
+    ) -> Result<TrainingReport, MultiMarkovError> {
+        self.trainer()
+            .try_untrain(sequences.map(|sequence| (sequence, 1.0)), 0)
+    }
+
+    /// Strict version of `untrain_weighted`.  See `try_untrain`.
+    pub fn try_untrain_weighted(
+        &mut self,
+        sequences: impl Iterator<Item = (impl AsRef<[T]>, f64)>,
+    ) -> Result<TrainingReport, MultiMarkovError> {
+        self.trainer().try_untrain(sequences, 0)
+    }
+
+    fn trainer(&mut self) -> Trainer<'_, T> {
+        Trainer {
+            markov_chain: &mut self.markov_chain,
+            known_states: &mut self.known_states,
+            training_set: &mut self.training_set,
+            order: self.order,
+            boundaries: self.boundaries.as_ref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn char_data() -> Vec<Vec<char>> {
+        vec![
+            vec!['a', 'c', 'e'],
+            vec!['f', 'o', 'o', 'b', 'a', 'r'],
+            vec!['b', 'a', 'z'],
+        ]
+    }
+
+    #[test]
+    fn built_models_can_keep_learning() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .with_prior(0.5)
+            .with_training_set()
+            .train(char_data().into_iter())
+            .build();
+        assert_eq!(mm.weight(&['b'], &'q'), None);
+        mm.train(vec![vec!['b', 'a', 'q'], vec!['x']].into_iter());
+        assert_eq!(mm.weight(&['a'], &'q'), Some(1.0));
+        assert_eq!(mm.weight(&['b', 'a'], &'q'), Some(1.0));
+        // the newly discovered state gets the prior
+        assert!(mm.known_states.contains(&'q'));
+        assert_eq!(mm.weight(&['b'], &'q'), Some(0.5));
+        assert!(mm
+            .training_set
+            .as_ref()
+            .unwrap()
+            .contains(&vec!['b', 'a', 'q']));
+    }
+
+    #[test]
+    fn models_can_go_back_to_the_builder() {
+        let new_data = vec![vec!['b', 'a', 'q'], vec!['z', 'o', 'o']];
+        let all_at_once = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter().chain(new_data.clone()))
+            .build();
+        let retrained = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .build()
+            .into_builder()
+            .train(new_data.into_iter())
+            .build();
+        assert_eq!(retrained.markov_chain, all_at_once.markov_chain);
+        assert_eq!(retrained.known_states, all_at_once.known_states);
+        assert_eq!(retrained.prior, all_at_once.prior);
+        assert_eq!(retrained.boundaries, all_at_once.boundaries);
+    }
+
+    #[test]
+    fn borrowed_sequences_train_like_owned_ones() {
+        let corpus: Vec<Vec<String>> = vec![
+            vec!["x".to_string()],
+            vec!["a".to_string(), "b".to_string()],
+            vec![
+                "b".to_string(),
+                "a".to_string(),
+                "b".to_string(),
+                "c".to_string(),
+            ],
+        ];
+        for boundaries in [false, true] {
+            let builder = || {
+                let builder = MultiMarkov::<String>::builder()
+                    .with_order(3)
+                    .with_training_set();
+                match boundaries {
+                    true => builder.with_boundaries("^".to_string(), "$".to_string()),
+                    false => builder,
+                }
+            };
+            let owned = builder().train(corpus.clone().into_iter()).build();
+            let borrowed = builder()
+                .train(corpus.iter().map(|sequence| &sequence[..]))
+                .build();
+            assert_eq!(borrowed.markov_chain, owned.markov_chain);
+            assert_eq!(borrowed.known_states, owned.known_states);
+            assert_eq!(borrowed.training_set, owned.training_set);
+        }
+        // "^ b a" and "a b $" are among the contexts and transitions of the last sequence
+        let mm = MultiMarkov::<String>::builder()
+            .with_order(3)
+            .with_boundaries("^".to_string(), "$".to_string())
+            .train(corpus.iter())
+            .build();
+        let context = |states: &[&str]| states.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(mm.markov_chain.contains_key(&context(&["^", "b", "a"])));
+        assert_eq!(
+            mm.weight(&context(&["a", "b"]), &"$".to_string()),
+            Some(1.0)
+        );
+    }
+
+    #[test]
+    fn weighted_sequences_count_their_weight() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .without_prior()
+            .train_weighted(
+                vec![
+                    (vec!['a', 'b', 'a'], 2.5),
+                    (vec!['a', 'b'], 0.5),
+                    (vec!['a', 'c'], 0.0),
+                    (vec!['a', 'c'], -1.0),
+                    (vec!['a', 'c'], f64::NAN),
+                ]
+                .into_iter(),
+            )
+            .train(vec![vec!['b', 'a']].into_iter())
+            .build();
+        assert_eq!(mm.weight(&['a'], &'b'), Some(3.0));
+        assert_eq!(mm.weight(&['b'], &'a'), Some(3.5));
+        assert_eq!(mm.weight(&['a', 'b'], &'a'), Some(2.5));
+        // the rejected weights trained nothing
+        assert_eq!(mm.weight(&['a'], &'c'), None);
+        assert!(!mm.known_states.contains(&'c'));
+    }
+
+    #[test]
+    fn training_reports_rejected_sequences() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .build();
+        let report = mm.train_weighted(
+            vec![
+                (vec!['a', 'q'], 1.0),
+                (vec![], 1.0),
+                (vec!['x'], 2.0),
+                (vec!['b', 'o'], 0.0),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(report.accepted, 2);
+        assert_eq!(
+            report.rejected,
+            vec![
+                (1, RejectionReason::Empty),
+                (3, RejectionReason::InvalidWeight)
+            ]
+        );
+        assert_eq!(report.sequences(), 4);
+        assert_eq!((report.shortest, report.longest), (Some(1), Some(2)));
+        assert_eq!(report.mean_length(), Some(1.5));
+        assert_eq!(report.new_states, 2);
+        assert_eq!(report.alphabet_size, mm.known_states.len());
+        assert_eq!(
+            report.to_string(),
+            "2 of 4 sequences trained (lengths 1 to 2, mean 1.50); 2 new states, 10 known states"
+        );
+    }
+
+    #[test]
+    fn builders_report_all_their_training() {
+        let builder = MultiMarkov::<char>::builder()
+            .train(vec![vec!['a', 'b'], vec!['c']].into_iter())
+            .train_weighted(vec![(vec!['a', 'b', 'c'], 2.0), (vec!['x'], 1.0)].into_iter());
+        let report = builder.training_report();
+        assert_eq!(report.accepted, 2);
+        assert_eq!(
+            report.rejected,
+            vec![
+                (1, RejectionReason::TooShort),
+                (3, RejectionReason::TooShort)
+            ]
+        );
+        assert_eq!((report.shortest, report.longest), (Some(2), Some(3)));
+        assert_eq!((report.new_states, report.alphabet_size), (2, 2));
+    }
+
+    #[test]
+    fn strict_training_rejects_the_whole_batch() {
+        let mut mm = MultiMarkov::<char>::builder()
+            .train(char_data().into_iter())
+            .build();
+        let before = mm.markov_chain.clone();
+        assert_eq!(
+            mm.try_train(vec![vec!['a', 'q'], vec!['x']].into_iter()),
+            Err(MultiMarkovError::RejectedSequence {
+                index: 1,
+                reason: RejectionReason::TooShort
+            })
+        );
+        assert_eq!(mm.markov_chain, before);
+        let report = mm.try_train(vec![vec!['a', 'q']].into_iter()).unwrap();
+        assert_eq!(report.accepted, 1);
+        assert!(mm.known_states.contains(&'q'));
+    }
+
+    #[test]
+    fn strict_training_keeps_the_builder_and_counts_across_calls() {
+        let mut builder =
+            MultiMarkov::<char>::builder().train(vec![vec!['a', 'b'], vec!['c']].into_iter());
+        assert_eq!(
+            builder
+                .try_train(vec![vec!['b', 'c'], vec!['d']].into_iter())
+                .map(|_| ()),
+            Err(MultiMarkovError::RejectedSequence {
+                index: 3,
+                reason: RejectionReason::TooShort
+            })
+        );
+        builder.try_train(vec![vec!['b', 'c']].into_iter()).unwrap();
+        let mm = builder.build();
+        assert_eq!(mm.weight(&['a'], &'b'), Some(1.0));
+        assert_eq!(mm.weight(&['b'], &'c'), Some(1.0));
+    }
+
+    #[test]
+    fn untraining_removes_exactly_what_training_added() {
+        let offensive = vec!['b', 'o', 'o', 'q'];
+        let clean = MultiMarkov::<char>::builder()
+            .with_order(3)
+            .with_boundaries('#', '#')
+            .with_training_set()
+            .train(char_data().into_iter())
+            .build();
+        let mut mm = MultiMarkov::<char>::builder()
+            .with_order(3)
+            .with_boundaries('#', '#')
+            .with_training_set()
+            .train(char_data().into_iter())
+            .train(vec![offensive.clone()].into_iter())
+            .build();
+        assert!(mm.known_states.contains(&'q'));
+        mm.untrain(vec![offensive.clone()].into_iter());
+        assert_eq!(mm.markov_chain, clean.markov_chain);
+        assert_eq!(mm.known_states, clean.known_states);
+        assert_eq!(mm.training_set, clean.training_set);
+        // a sequence that isn't in the model leaves it untouched
+        mm.untrain(vec![offensive, vec!['a', 'c', 'e', 'e']].into_iter());
+        assert_eq!(mm.markov_chain, clean.markov_chain);
+    }
+
+    #[test]
+    fn weighted_untraining_removes_weighted_training() {
+        let clean = MultiMarkov::<char>::builder()
+            .train(char_data().into_iter())
+            .build();
+        let mut mm = MultiMarkov::<char>::builder()
+            .train(char_data().into_iter())
+            .train_weighted(vec![(vec!['a', 'q'], 5.0)].into_iter())
+            .train_weighted((0..10).map(|_| (vec!['q', 'x'], 0.1)))
+            .build();
+        mm.untrain_weighted(vec![(vec!['a', 'q'], 5.0)].into_iter());
+        assert_eq!(mm.weight(&['a'], &'q'), None);
+        // the ten weights of 0.1 add up to a little less than 1.0
+        assert!(mm.weight(&['q'], &'x').unwrap() < 1.0);
+        mm.untrain_weighted(vec![(vec!['q', 'x'], 1.0)].into_iter());
+        assert_eq!(mm.markov_chain, clean.markov_chain);
+        assert_eq!(mm.known_states, clean.known_states);
+    }
+
+    #[test]
+    fn untraining_reports_sequences_that_were_never_trained() {
+        let builder = MultiMarkov::<char>::builder()
+            .with_boundaries('#', '#')
+            .train(char_data().into_iter())
+            .untrain(vec![vec!['b', 'a', 'z'], vec!['q', 'u', 'x']].into_iter())
+            .untrain(vec![vec!['b', 'a', 'z']].into_iter());
+        let report = builder.untraining_report();
+        assert_eq!(report.accepted, 1);
+        assert_eq!(
+            report.rejected,
+            vec![
+                (1, RejectionReason::NotTrained),
+                (2, RejectionReason::NotTrained)
+            ]
+        );
+        assert_eq!(report.new_states, 0);
+
+        let mut mm = builder.build();
+        let before = mm.markov_chain.clone();
+        // "ace" was only trained once, so it can only be untrained once
+        assert_eq!(
+            mm.try_untrain(vec![vec!['a', 'c', 'e'], vec!['a', 'c', 'e']].into_iter()),
+            Err(MultiMarkovError::RejectedSequence {
+                index: 1,
+                reason: RejectionReason::NotTrained
+            })
+        );
+        assert_eq!(mm.markov_chain, before);
+        let report = mm
+            .try_untrain(vec![vec!['a', 'c', 'e']].into_iter())
+            .unwrap();
+        assert_eq!(report.accepted, 1);
+        assert!(!mm.known_states.contains(&'e'));
+    }
+
+    #[test]
+    fn untraining_keeps_states_that_are_still_observed() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(2)
+            .train(char_data().into_iter())
+            .train(vec![vec!['z', 'a'], vec!['z', 'a']].into_iter())
+            .untrain(vec![vec!['z', 'a'], vec!['b', 'a', 'z']].into_iter())
+            .build();
+        assert_eq!(mm.weight(&['z'], &'a'), Some(1.0));
+        assert!(mm.known_states.contains(&'a'));
+        assert!(!mm.known_states.contains(&'z'));
+        // "foobar" still needs the context "b a", but not its transition to 'z'
+        assert!(!mm.markov_chain[&vec!['b', 'a']].contains_key(&'z'));
+    }
+
+    #[test]
+    fn untraining_forgets_only_states_that_are_no_longer_observed() {
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .train(vec![vec!['z', 'a'], vec!['y', 'a'], vec!['a', 'x']].into_iter())
+            .untrain(vec![vec!['y', 'a'], vec!['a', 'x']].into_iter())
+            .build();
+        assert_eq!(mm.known_states, BTreeSet::from(['a']));
+
+        let mm = MultiMarkov::<char>::builder()
+            .with_order(1)
+            .with_boundaries('^', '$')
+            .train(vec![vec!['z', 'a'], vec!['y', 'a']].into_iter())
+            .untrain(vec![vec!['y', 'a']].into_iter())
+            .build();
+        assert_eq!(mm.known_states, BTreeSet::from(['a', 'z']));
+    }
+}
@@ -0,0 +1,305 @@
+use crate::builder::MultiMarkovBuilder;
+use crate::{check_prior, MultiMarkov};
+use std::fmt;
+use std::hash::Hash;
+
+/// A hyperparameter search over the order and prior of a model, by k-fold cross-validation.
+/// For each combination of order and prior in the grid, the corpus is split into `folds` parts;
+/// a model is trained on all but one part and evaluated on the part that was held out, until
+/// each part has been held out once.  Combinations are ranked by their held-out perplexity.
+///
+/// ```
+/// use multimarkov::tuning::GridSearch;
+/// let corpus: Vec<Vec<char>> = ["anna", "hannah", "nan", "ana", "nana", "hahn"]
+///     .iter()
+///     .map(|name| name.chars().collect())
+///     .collect();
+/// let report = GridSearch::new()
+///     .with_orders(vec![1, 2, 3])
+///     .with_priors(vec![0.001, 0.01])
+///     .with_folds(3)
+///     .with_boundaries('#', '#')
+///     .run(&corpus);
+/// println!("{}", report);
+/// let mm = report.best_builder().unwrap().train(corpus.into_iter()).build();
+/// ```
+pub struct GridSearch<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    orders: Vec<i32>,
+    priors: Vec<f64>,
+    folds: usize,
+    boundaries: Option<(T, T)>,
+}
+
+/// The held-out performance of one combination of order and prior in a `GridSearch`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningResult {
+    pub order: i32,
+    /// The prior, or `0.0` for a model without priors.
+    pub prior: f64,
+    /// The perplexity over all folds' held-out sequences together, or `None` if nothing could be
+    /// scored.
+    pub perplexity: Option<f64>,
+    /// The number of held-out sequences that were scored, over all folds.
+    pub scored_sequences: usize,
+    /// The number of occurrences of out-of-vocabulary states in held-out sequences, over all folds.
+    pub oov_states: usize,
+    /// The number of held-out sequences that could not be scored because no trained model matched
+    /// them, over all folds.
+    pub unscorable_sequences: usize,
+}
+
+/// The results of a `GridSearch`, best first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningReport<T> {
+    pub results: Vec<TuningResult>,
+    boundaries: Option<(T, T)>,
+}
+
+impl<T> GridSearch<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    pub const DEFAULT_FOLDS: usize = 5;
+
+    /// A search over orders 1 to 3, and the default prior or no prior at all.
+    pub fn new() -> Self {
+        Self {
+            orders: vec![1, 2, 3],
+            priors: vec![0.0, MultiMarkov::<T>::DEFAULT_PRIOR],
+            folds: Self::DEFAULT_FOLDS,
+            boundaries: None,
+        }
+    }
+
+    /// The orders to try.  Each must be a positive integer.
+    pub fn with_orders(mut self, orders: Vec<i32>) -> Self {
+        assert!(
+            orders.iter().all(|order| *order > 0),
+            "Order must be an integer greater than zero."
+        );
+        self.orders = orders;
+        self
+    }
+
+    /// The priors to try.  Use `0.0` to try a model without priors.  Each must be a finite
+    /// number, zero or greater.
+    pub fn with_priors(mut self, priors: Vec<f64>) -> Self {
+        assert!(
+            priors.iter().all(|prior| check_prior(*prior).is_ok()),
+            "Prior must be a finite number, zero or greater."
+        );
+        self.priors = priors;
+        self
+    }
+
+    /// The number of parts to split the corpus into.  Must be at least 2.
+    ///
+    /// The default is GridSearch::DEFAULT_FOLDS
+    pub fn with_folds(mut self, folds: usize) -> Self {
+        assert!(folds >= 2, "Cross-validation needs at least two folds.");
+        self.folds = folds;
+        self
+    }
+
+    /// The boundary states to train every model with.  See `MultiMarkovBuilder::with_boundaries`.
+    pub fn with_boundaries(mut self, start: T, end: T) -> Self {
+        self.boundaries = Some((start, end));
+        self
+    }
+
+    /// Run the search on a corpus of sequences.  Sequence `i` is held out in fold `i % folds`.
+    pub fn run(&self, corpus: &[Vec<T>]) -> TuningReport<T> {
+        let mut results = Vec::new();
+        for order in &self.orders {
+            for prior in &self.priors {
+                results.push(self.cross_validate(corpus, *order, *prior));
+            }
+        }
+        // lowest perplexity first; results with no (or a NaN) perplexity come last
+        let ranked = |result: &TuningResult| result.perplexity.filter(|p| !p.is_nan());
+        results.sort_by(|a, b| match (ranked(a), ranked(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+        TuningReport {
+            results,
+            boundaries: self.boundaries.clone(),
+        }
+    }
+
+    fn cross_validate(&self, corpus: &[Vec<T>], order: i32, prior: f64) -> TuningResult {
+        let mut result = TuningResult {
+            order,
+            prior,
+            perplexity: None,
+            scored_sequences: 0,
+            oov_states: 0,
+            unscorable_sequences: 0,
+        };
+        let mut total_log_likelihood = 0.0;
+        let mut scored_transitions: usize = 0;
+        for fold in 0..self.folds {
+            let training = corpus
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| i % self.folds != fold)
+                .map(|(_, sequence)| sequence);
+            let held_out = corpus
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| i % self.folds == fold)
+                .map(|(_, sequence)| sequence);
+            let mm = configured_builder(order, prior, &self.boundaries)
+                .train(training)
+                .build();
+            let report = mm.evaluate(held_out);
+            total_log_likelihood += report.total_log_likelihood;
+            scored_transitions += report.scored_transitions;
+            result.scored_sequences += report.scored_sequences;
+            result.oov_states += report.oov_states;
+            result.unscorable_sequences += report.unscorable_sequences.len();
+        }
+        if scored_transitions > 0 {
+            result.perplexity = Some((-total_log_likelihood / scored_transitions as f64).exp());
+        }
+        result
+    }
+}
+
+impl<T> Default for GridSearch<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> TuningReport<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    /// The combination of order and prior with the lowest held-out perplexity.
+    pub fn best(&self) -> Option<&TuningResult> {
+        self.results
+            .first()
+            .filter(|best| best.perplexity.is_some_and(|p| !p.is_nan()))
+    }
+
+    /// A builder configured with the best order and prior (and the search's boundaries), ready
+    /// to be trained on the full corpus.
+    pub fn best_builder(&self) -> Option<MultiMarkovBuilder<T>> {
+        self.best()
+            .map(|best| configured_builder(best.order, best.prior, &self.boundaries))
+    }
+}
+
+impl<T> fmt::Display for TuningReport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (rank, result) in self.results.iter().enumerate() {
+            let perplexity = match result.perplexity {
+                Some(perplexity) => format!("{:.4}", perplexity),
+                None => String::from("n/a"),
+            };
+            writeln!(
+                f,
+                "{}. order {}, prior {}: perplexity {} ({} sequences scored, {} out-of-vocabulary states, {} unscorable)",
+                rank + 1,
+                result.order,
+                result.prior,
+                perplexity,
+                result.scored_sequences,
+                result.oov_states,
+                result.unscorable_sequences
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn configured_builder<T>(
+    order: i32,
+    prior: f64,
+    boundaries: &Option<(T, T)>,
+) -> MultiMarkovBuilder<T>
+where
+    T: Eq + Hash + Clone + std::cmp::Ord,
+{
+    let mut builder = MultiMarkov::<T>::builder()
+        .with_order(order)
+        .with_prior(prior);
+    if let Some((start, end)) = boundaries {
+        builder = builder.with_boundaries(start.clone(), end.clone());
+    }
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus() -> Vec<Vec<char>> {
+        [
+            "abab", "baba", "abba", "baab", "abab", "baba", "aabb", "bbaa",
+        ]
+        .iter()
+        .map(|s| s.chars().collect())
+        .collect()
+    }
+
+    #[test]
+    fn grid_search_ranks_every_combination() {
+        let report = GridSearch::new()
+            .with_orders(vec![1, 2])
+            .with_priors(vec![0.0, 0.01, 0.1])
+            .with_folds(4)
+            .with_boundaries('#', '#')
+            .run(&corpus());
+        assert_eq!(report.results.len(), 6);
+        let perplexities: Vec<f64> = report
+            .results
+            .iter()
+            .map(|result| result.perplexity.unwrap())
+            .collect();
+        assert!(perplexities.windows(2).all(|pair| pair[0] <= pair[1]));
+        // every held-out sequence is scored in some fold
+        assert!(report
+            .results
+            .iter()
+            .all(|result| result.scored_sequences == 8));
+        // without priors, some held-out transitions are impossible
+        assert_eq!(report.results.last().unwrap().prior, 0.0);
+        assert_eq!(
+            report.results.last().unwrap().perplexity,
+            Some(f64::INFINITY)
+        );
+        assert_eq!(report.to_string().lines().count(), 6);
+    }
+
+    #[test]
+    fn best_builder_uses_the_winning_settings() {
+        let report = GridSearch::new()
+            .with_orders(vec![1, 2])
+            .with_priors(vec![0.01])
+            .with_folds(2)
+            .with_boundaries('#', '#')
+            .run(&corpus());
+        let best = report.best().unwrap().clone();
+        let mm = report
+            .best_builder()
+            .unwrap()
+            .train(corpus().into_iter())
+            .build();
+        assert_eq!(mm.order, best.order);
+        assert!(mm.boundaries.is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Prior must be a finite number, zero or greater.")]
+    fn priors_must_be_valid() {
+        let _search = GridSearch::<char>::new().with_priors(vec![0.01, f64::NAN]);
+    }
+}